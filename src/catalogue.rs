@@ -0,0 +1,311 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Display,
+    io::{self, Write},
+    iter, mem,
+};
+
+use crate::{
+    error::{Cause, Error, Source},
+    game_parser::RareMoveWithLink,
+    util::{read_rare_moves, write_catalogue},
+    Result,
+};
+
+// rare_checkmates.csv and whatever `.moves` outputs get merged into it
+#[derive(Debug, Default)]
+pub struct Catalogue {
+    rare_moves: Vec<RareMoveWithLink>,
+}
+
+impl Catalogue {
+    // text rows and json lines can be mixed, empty lines are skipped
+    pub fn load(&mut self, s: &str) -> std::result::Result<(), Source> {
+        for line in s.lines().map(str::trim_end).filter(|line| !line.is_empty()) {
+            self.rare_moves.push(if line.starts_with('{') {
+                serde_json::from_str(line)?
+            } else {
+                line.parse()?
+            });
+        }
+        Ok(())
+    }
+
+    // one row per move of a game, then one per move in a position, from the earliest game
+    pub fn tidy(&mut self) {
+        let mut rare_moves = mem::take(&mut self.rare_moves);
+        // a row with the game context wins over the legacy row of the same move
+        rare_moves.sort_by_key(|rare_move| rare_move.fen.is_empty());
+        let mut events = HashSet::new();
+        rare_moves.retain(|rare_move| {
+            events.insert((
+                rare_move.game_link.clone(),
+                rare_move.ply,
+                rare_move.san.clone(),
+                rare_move.move_type.to_string(),
+            ))
+        });
+
+        // every sort here is stable and legacy rows compare equal, so they keep their order
+        rare_moves.sort_by_cached_key(|rare_move| {
+            (
+                rare_move.date,
+                (!rare_move.fen.is_empty()).then(|| (rare_move.game_link.clone(), rare_move.ply)),
+            )
+        });
+        let mut positions = HashSet::new();
+        rare_moves.retain(|rare_move| {
+            rare_move.fen.is_empty() || positions.insert(rare_move.position_key())
+        });
+
+        // legacy rows have no date and keep the order they were loaded in
+        rare_moves.sort_by_cached_key(|rare_move| {
+            (
+                rare_move.date,
+                (!rare_move.fen.is_empty()).then(|| {
+                    (
+                        rare_move.move_type.to_string(),
+                        rare_move.game_link.clone(),
+                        rare_move.ply,
+                    )
+                }),
+            )
+        });
+        self.rare_moves = rare_moves;
+    }
+
+    pub fn rare_moves(&self) -> &[RareMoveWithLink] {
+        &self.rare_moves
+    }
+
+    // the first row of a category in catalogue order is its earliest occurrence
+    pub fn summary(&self) -> Summary {
+        let mut summary = Summary::default();
+        for rare_move in &self.rare_moves {
+            let code = rare_move.move_type.to_string();
+            let missed = code.ends_with('?');
+            let counts = summary
+                .0
+                .entry((
+                    rare_move.move_type.name(),
+                    code.trim_end_matches('?').trim_end().to_string(),
+                ))
+                .or_insert_with(|| Counts {
+                    first: first_occurrence(rare_move),
+                    ..Counts::default()
+                });
+            if missed {
+                counts.missed += 1;
+            } else {
+                counts.played += 1;
+            }
+        }
+        summary
+    }
+}
+
+impl Display for Catalogue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.rare_moves
+            .iter()
+            .try_for_each(|rare_move| writeln!(f, "{rare_move}"))
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Counts {
+    pub played: u64,
+    pub missed: u64,
+    // date and link to the position after the move, "-" for legacy rows without a date
+    pub first: String,
+}
+
+fn first_occurrence(rare_move: &RareMoveWithLink) -> String {
+    let date = if rare_move.fen.is_empty() {
+        "-".to_string()
+    } else {
+        rare_move.date.to_string()
+    };
+    format!("{date:<10} {}#{}", rare_move.game_link, rare_move.ply + 1)
+}
+
+// keyed by variant and type code without the missed marker, so captures and checks get their own row
+#[derive(Debug, Default)]
+pub struct Summary(BTreeMap<(&'static str, String), Counts>);
+
+impl Summary {
+    fn total(&self) -> Counts {
+        self.0
+            .values()
+            .fold(Counts::default(), |total, counts| Counts {
+                played: total.played + counts.played,
+                missed: total.missed + counts.missed,
+                first: String::new(),
+            })
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:<22}{:<6}{:>8}{:>8}  first",
+            "variant", "code", "played", "missed"
+        )?;
+        for ((variant, code), counts) in &self.0 {
+            writeln!(
+                f,
+                "{variant:<22}{code:<6}{:>8}{:>8}  {}",
+                counts.played, counts.missed, counts.first
+            )?;
+        }
+        let total = self.total();
+        writeln!(f, "{:<28}{:>8}{:>8}", "total", total.played, total.missed)
+    }
+}
+
+// `catalogue rare_checkmates.csv 2020-01.moves ...` merges the outputs into the catalogue
+pub fn run(args: &[String]) -> Result<()> {
+    let Some((path, moves_files)) = args.split_first() else {
+        return Err(Error::config(Cause::UnexpectedValue(
+            "catalogue <catalogue> <moves>...".to_string(),
+        )));
+    };
+    let mut catalogue = Catalogue::default();
+    let files = iter::once((path, false)).chain(moves_files.iter().map(|file| (file, true)));
+    for (file, required) in files {
+        if let Some(s) = read_rare_moves(file, required)? {
+            catalogue.load(&s).map_err(Error::decode)?;
+        }
+    }
+    catalogue.tidy();
+    write_catalogue(path, &catalogue)?;
+    write!(io::stdout().lock(), "{}", catalogue.summary()).map_err(Error::output)
+}
+
+#[cfg(test)]
+#[allow(clippy::panic_in_result_fn)]
+mod tests {
+    use super::*;
+
+    type Result<T> = std::result::Result<T, Source>;
+
+    const FEN: &str = "8/5k2/5n2/8/8/8/8/4K3 b - - 0 40";
+    const ROW: &str =
+        " 40... Kxf7#  ,K x  ,https://lichess.org/abcdefgh,Kxf7#,alice,1500,bob,1490,2017-03-04,180+0,";
+
+    fn row(game_link: &str, date: &str, fen: &str) -> String {
+        let row = ROW
+            .replace("abcdefgh", game_link)
+            .replace("2017-03-04", date);
+        format!("{row}{fen},1,true,false")
+    }
+
+    #[test]
+    fn test_tidy_keeps_the_earliest_game() -> Result<()> {
+        let later_fen = FEN.replace(" 40", " 52");
+        let rows = [
+            row("later000", "2018-01-01", &later_fen),
+            row("earliest", "2017-03-04", FEN),
+            row("earliest", "2017-03-04", FEN),
+            // the legacy row of a move that now has its context
+            " 40... Kxf7#  ,K x  ,https://lichess.org/earliest".to_string(),
+            " 12... Kxf2#  ,K x ?,https://lichess.org/legacy00".to_string(),
+        ];
+        let mut catalogue = Catalogue::default();
+        catalogue.load(&rows.join("\n"))?;
+        catalogue.tidy();
+
+        assert_eq!(
+            catalogue
+                .rare_moves
+                .iter()
+                .map(|rare_move| rare_move.game_link.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "https://lichess.org/legacy00",
+                "https://lichess.org/earliest"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_tidy_keeps_the_order_of_legacy_rows() -> Result<()> {
+        let rows = [
+            row("earliest", "2017-03-04", FEN),
+            " 20.   Nf8e6# ,DD 2 ,https://lichess.org/legacy01".to_string(),
+            " 12... Kxf2#  ,K x ?,https://lichess.org/legacy00".to_string(),
+        ];
+        let mut catalogue = Catalogue::default();
+        catalogue.load(&rows.join("\n"))?;
+        catalogue.tidy();
+
+        assert_eq!(
+            catalogue
+                .rare_moves
+                .iter()
+                .map(|rare_move| rare_move.game_link.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "https://lichess.org/legacy01",
+                "https://lichess.org/legacy00",
+                "https://lichess.org/earliest"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_catalogue_roundtrip() -> Result<()> {
+        let rows = format!(
+            " 12... Kxf2#  ,K x ?,https://lichess.org/legacy00\n{}\n",
+            row("earliest", "2017-03-04", FEN)
+        );
+        let mut catalogue = Catalogue::default();
+        catalogue.load(&rows)?;
+        catalogue.tidy();
+
+        assert_eq!(catalogue.to_string(), rows);
+        Ok(())
+    }
+
+    #[test]
+    fn test_summary() -> Result<()> {
+        let rows = [
+            " 12... Kxf2#  ,K x ?,https://lichess.org/legacy00",
+            " 40... Kxf7#  ,K x  ,https://lichess.org/legacy01",
+            " 20.   Kf3#   ,K   ?,https://lichess.org/legacy02",
+            " 56.   Kxf5#  ,K x ?,https://lichess.org/legacy03",
+        ];
+        let mut catalogue = Catalogue::default();
+        catalogue.load(&format!(
+            "{}\n{}",
+            rows.join("\n"),
+            row("later000", "2018-01-01", FEN)
+        ))?;
+        let summary = catalogue.summary();
+
+        assert_eq!(
+            summary.0.get(&("king", "K x".to_string())),
+            Some(&Counts {
+                played: 2,
+                missed: 2,
+                first: "-          https://lichess.org/legacy00#24".to_string(),
+            })
+        );
+        assert_eq!(
+            summary.0.get(&("king", "K".to_string())),
+            Some(&Counts {
+                played: 0,
+                missed: 1,
+                first: "-          https://lichess.org/legacy02#39".to_string(),
+            })
+        );
+        assert_eq!(
+            summary.to_string().lines().last(),
+            Some("total                              2       3")
+        );
+        Ok(())
+    }
+}
@@ -1,9 +1,15 @@
 use std::{collections::HashSet, time::Duration};
 
 use serde::Deserialize;
-use serde_with::serde_as;
+use serde_with::{serde_as, DisplayFromStr};
 
-use crate::Result;
+use crate::{
+    error::Error,
+    game_parser::{Clock, DetectorKind, ForcedMateSearch, GameData, PositionCensus},
+    openings::OpeningTree,
+    records::{Leaderboard, Metric},
+    Result,
+};
 
 #[serde_with::serde_as]
 #[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -15,18 +21,204 @@ pub struct Config {
     #[serde(alias = "update_interval_seconds")]
     pub update_interval: Duration,
     pub output: Output,
+    #[serde(default)]
+    pub filter: Filter,
+    #[serde(default)]
+    pub detectors: Detectors,
+    #[serde(default)]
+    pub forced_mates: ForcedMates,
+    #[serde(default)]
+    pub records: Records,
+    #[serde(default)]
+    pub census: Census,
+    #[serde(default)]
+    pub openings: Openings,
 }
 
+// each bool switches one output file on or off
 #[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Output {
     pub rare_moves: bool,
     pub data: bool,
+    #[serde(default)]
+    pub errors: bool,
+    #[serde(default)]
+    pub rare_moves_format: RareMovesFormat,
+    #[serde(default)]
+    pub dedupe: Dedupe,
+    #[serde(default)]
+    pub rare_pgn: bool,
+}
+
+#[derive(Deserialize, Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RareMovesFormat {
+    // fixed width rows like rare_checkmates.csv, written to <month>.moves
+    #[default]
+    Text,
+    // one object per line, written to <month>.moves.jsonl
+    JsonLines,
+}
+
+#[derive(Deserialize, Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Dedupe {
+    // a rare move repeated over several plies of a game is logged once
+    #[default]
+    Game,
+    // and left out of later games reaching the same position
+    Run,
+}
+
+#[serde_as]
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Filter {
+    #[serde_as(as = "Option<HashSet<DisplayFromStr>>")]
+    pub time_controls: Option<HashSet<Clock>>,
+    #[serde(default)]
+    pub include_from_position: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Detectors {
+    #[serde(default = "DetectorKind::all")]
+    pub enabled: Vec<DetectorKind>,
+}
+
+impl Default for Detectors {
+    fn default() -> Self {
+        Self {
+            enabled: DetectorKind::all(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct ForcedMates {
+    pub enabled: bool,
+    pub max_depth: u8,
+    pub max_forcing_moves: usize,
+    pub log_min_elo: i16,
+}
+
+impl Default for ForcedMates {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_depth: 2,
+            max_forcing_moves: 8,
+            log_min_elo: 2200,
+        }
+    }
+}
+
+impl ForcedMates {
+    pub fn search(&self) -> Option<ForcedMateSearch> {
+        self.enabled.then(|| ForcedMateSearch {
+            max_depth: self.max_depth.clamp(2, 3),
+            max_forcing_moves: self.max_forcing_moves,
+            log_min_elo: self.log_min_elo,
+        })
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Records {
+    pub size: usize,
+    pub metrics: Vec<Metric>,
+}
+
+impl Default for Records {
+    fn default() -> Self {
+        Self {
+            size: 10,
+            metrics: Metric::ALL.to_vec(),
+        }
+    }
+}
+
+impl Records {
+    pub fn leaderboard(&self) -> Leaderboard {
+        Leaderboard::new(self.size, self.metrics.clone())
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Census {
+    pub enabled: bool,
+    pub from_move: u16,
+    pub sketch_width: usize,
+    pub max_candidates: usize,
+    pub max_common: usize,
+}
+
+impl Default for Census {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            from_move: 20,
+            sketch_width: 1 << 22,
+            max_candidates: 1_000_000,
+            max_common: 1000,
+        }
+    }
+}
+
+impl Census {
+    pub fn position_census(&self) -> Option<PositionCensus> {
+        self.enabled.then(|| {
+            PositionCensus::new(
+                self.from_move,
+                self.sketch_width,
+                self.max_candidates,
+                self.max_common,
+            )
+        })
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Openings {
+    pub enabled: bool,
+    pub plies: usize,
+    pub json_min_games: u64,
+}
+
+impl Default for Openings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            plies: 10,
+            json_min_games: 1000,
+        }
+    }
+}
+
+impl Openings {
+    pub fn tree(&self) -> Option<OpeningTree> {
+        self.enabled.then(|| OpeningTree::new(self.plies))
+    }
+}
+
+impl Filter {
+    pub fn accepts(&self, game_data: &GameData) -> bool {
+        (self.include_from_position || !game_data.from_position)
+            && self
+                .time_controls
+                .as_ref()
+                .is_none_or(|time_controls| time_controls.contains(&game_data.clock))
+    }
 }
 
 impl Config {
     pub fn from_file() -> Result<Self> {
-        let s = std::fs::read_to_string("config.toml")?;
-        Ok(toml::from_str(&s)?)
+        let s = std::fs::read_to_string("config.toml").map_err(Error::config)?;
+        toml::from_str(&s).map_err(Error::config)
     }
 }
 
@@ -64,10 +256,149 @@ data = false
             output: Output {
                 rare_moves: true,
                 data: false,
+                errors: false,
+                rare_moves_format: RareMovesFormat::Text,
+                dedupe: Dedupe::Game,
+                rare_pgn: false,
             },
+            filter: Filter::default(),
+            detectors: Detectors::default(),
+            forced_mates: ForcedMates::default(),
+            records: Records::default(),
+            census: Census::default(),
+            openings: Openings::default(),
         };
 
         assert_eq!(config, expected);
         Ok(())
     }
+
+    #[test]
+    #[allow(clippy::panic_in_result_fn)]
+    fn test_config_time_control_filter() -> Result<()> {
+        let s = format!("{TEST_CONFIG}\n[filter]\ntime_controls = [\"180+0\", \"-\"]\n");
+        let config: Config = toml::from_str(&s)?;
+        let blitz = Clock {
+            base_seconds: 180,
+            increment_seconds: 0,
+        };
+        let expected = [blitz, Clock::default()].into_iter().collect();
+
+        assert_eq!(config.filter.time_controls, Some(expected));
+
+        let mut game_data = GameData {
+            clock: blitz,
+            ..Default::default()
+        };
+        assert!(config.filter.accepts(&game_data));
+        game_data.clock.increment_seconds = 2;
+        assert!(!config.filter.accepts(&game_data));
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::panic_in_result_fn)]
+    fn test_config_detectors() -> Result<()> {
+        let s = format!("{TEST_CONFIG}\n[detectors]\nenabled = [\"king_capture\"]\n");
+        let config: Config = toml::from_str(&s)?;
+
+        assert_eq!(config.detectors.enabled, vec![DetectorKind::KingCapture]);
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::panic_in_result_fn)]
+    fn test_config_forced_mates() -> Result<()> {
+        assert_eq!(ForcedMates::default().search(), None);
+
+        let s = format!("{TEST_CONFIG}\n[forced_mates]\nenabled = true\nmax_depth = 5\n");
+        let config: Config = toml::from_str(&s)?;
+        let search = config.forced_mates.search();
+
+        assert_eq!(search.map(|search| search.max_depth), Some(3));
+        assert_eq!(config.forced_mates.log_min_elo, 2200);
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::panic_in_result_fn)]
+    fn test_config_records() -> Result<()> {
+        let s = format!("{TEST_CONFIG}\n[records]\nmetrics = [\"half_moves\", \"queens\"]\n");
+        let config: Config = toml::from_str(&s)?;
+
+        assert_eq!(config.records.size, 10);
+        assert_eq!(
+            config.records.metrics,
+            vec![Metric::HalfMoves, Metric::Queens]
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::panic_in_result_fn)]
+    fn test_config_census() -> Result<()> {
+        let config: Config = toml::from_str(TEST_CONFIG)?;
+        assert!(config.census.position_census().is_none());
+
+        let s = format!("{TEST_CONFIG}\n[census]\nenabled = true\nfrom_move = 30\n");
+        let enabled: Config = toml::from_str(&s)?;
+
+        assert_eq!(
+            (
+                enabled.census.from_move,
+                enabled.census.max_candidates,
+                enabled.census.max_common
+            ),
+            (30, 1_000_000, 1000)
+        );
+        assert!(enabled.census.position_census().is_some());
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::panic_in_result_fn)]
+    fn test_config_openings() -> Result<()> {
+        let s = format!("{TEST_CONFIG}\n[openings]\nenabled = true\nplies = 6\n");
+        let config: Config = toml::from_str(&s)?;
+
+        assert_eq!(config.openings.plies, 6);
+        assert_eq!(config.openings.json_min_games, 1000);
+        assert!(config.openings.tree().is_some());
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::panic_in_result_fn)]
+    fn test_config_rare_moves_format() -> Result<()> {
+        let s = TEST_CONFIG.replace(
+            "data = false",
+            "data = false\nrare_moves_format = \"json_lines\"",
+        );
+        let config: Config = toml::from_str(&s)?;
+
+        assert_eq!(config.output.rare_moves_format, RareMovesFormat::JsonLines);
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::panic_in_result_fn)]
+    fn test_config_dedupe() -> Result<()> {
+        let s = TEST_CONFIG.replace("data = false", "data = false\ndedupe = \"run\"");
+        let config: Config = toml::from_str(&s)?;
+
+        assert_eq!(config.output.dedupe, Dedupe::Run);
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_from_position() {
+        let game_data = GameData {
+            from_position: true,
+            ..Default::default()
+        };
+        let mut filter = Filter::default();
+        assert!(!filter.accepts(&game_data));
+        filter.include_from_position = true;
+        assert!(filter.accepts(&game_data));
+    }
 }
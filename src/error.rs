@@ -1,73 +1,212 @@
-use derive_more::From;
 use itertools::Itertools;
-use rerun::RecordingStreamError;
-use shakmaty::san::SanError;
 use std::{fmt::Display, sync::PoisonError};
 
 pub type Result<T> = std::result::Result<T, Error>;
+pub type Source = Box<dyn std::error::Error + Send + Sync + 'static>;
 
-#[derive(Debug, From)]
-pub enum Error {
-    InvalidMove(SanError, String),
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    Config,
+    Download,
+    Decode,
+    Header(&'static str),
+    Move(String),
+    Output,
+    Plotting,
+    Ui,
+    Lock,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Context {
+    pub file: Option<String>,
+    pub game: Option<String>,
+    pub ply: Option<u16>,
+}
+
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    context: Context,
+    source: Source,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cause {
+    UnexpectedValue(String),
     InvalidFilename(String),
     NoContentLength,
-    CheckmateType,
-    #[from]
-    ParseString(String),
-    #[from]
-    ParseBuffer(Vec<u8>),
-    Mutex,
-    #[from]
-    Io(std::io::Error),
-    #[from]
-    Utf8(std::str::Utf8Error),
-    ParseInt(std::num::ParseIntError),
-    #[from]
-    Reqwest(reqwest::Error),
-    #[from]
-    ParseDate(chrono::format::ParseError),
-    #[from]
-    Plotting(RecordingStreamError),
-    #[from]
-    Toml(toml::de::Error),
-    Ui,
+    NotProcessing,
+    Poisoned,
+}
+
+impl Error {
+    fn new(kind: ErrorKind, source: impl Into<Source>) -> Self {
+        Self {
+            kind,
+            context: Context::default(),
+            source: source.into(),
+        }
+    }
+
+    pub fn config(source: impl Into<Source>) -> Self {
+        Self::new(ErrorKind::Config, source)
+    }
+
+    pub fn download(source: impl Into<Source>) -> Self {
+        Self::new(ErrorKind::Download, source)
+    }
+
+    pub fn decode(source: impl Into<Source>) -> Self {
+        Self::new(ErrorKind::Decode, source)
+    }
+
+    pub fn header(name: &'static str, source: impl Into<Source>) -> Self {
+        Self::new(ErrorKind::Header(name), source)
+    }
+
+    pub fn invalid_move(san: String, source: impl Into<Source>) -> Self {
+        Self::new(ErrorKind::Move(san), source)
+    }
+
+    pub fn output(source: impl Into<Source>) -> Self {
+        Self::new(ErrorKind::Output, source)
+    }
+
+    pub fn plotting(source: impl Into<Source>) -> Self {
+        Self::new(ErrorKind::Plotting, source)
+    }
+
+    pub fn ui(source: impl Into<Source>) -> Self {
+        Self::new(ErrorKind::Ui, source)
+    }
+
+    #[must_use]
+    pub const fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    #[must_use]
+    pub const fn context(&self) -> &Context {
+        &self.context
+    }
+
+    #[must_use]
+    pub fn in_file(mut self, file: &str) -> Self {
+        self.context.file.get_or_insert_with(|| file.to_string());
+        self
+    }
+
+    #[must_use]
+    pub fn in_game(mut self, game: &str) -> Self {
+        self.context.game.get_or_insert_with(|| game.to_string());
+        self
+    }
+
+    #[must_use]
+    pub fn at_ply(mut self, ply: usize) -> Self {
+        self.context.ply.get_or_insert(ply as u16);
+        self
+    }
+
+    // the error followed by all of its sources, for the UI and the logs
+    pub fn report(&self) -> String {
+        std::iter::successors(Some(self as &dyn std::error::Error), |e| e.source())
+            .map(ToString::to_string)
+            .join(": ")
+    }
 }
 
 impl<T> From<PoisonError<T>> for Error {
     fn from(_: PoisonError<T>) -> Self {
-        Self::Mutex
+        Self::new(ErrorKind::Lock, Cause::Poisoned)
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
 
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::InvalidMove(err, move_san) => {
-                write!(f, "Invalid move: {err} for move {move_san}")
-            }
-            Self::InvalidFilename(s) => write!(f, "Invalid filename: {s}"),
-            Self::NoContentLength => write!(f, "No content length"),
-            Self::CheckmateType => write!(f, "Checkmate type"),
-            Self::ParseString(s) => write!(f, "Parsing error: {s}"),
-            Self::ParseBuffer(buffer) => {
-                write!(f, "Parsing error: [{}]", comma_separated(buffer))
-            }
-            Self::Mutex => write!(f, "Mutex error"),
-            Self::Io(e) => write!(f, "IO error: {e}"),
-            Self::Utf8(e) => write!(f, "UTF-8 error: {e}"),
-            Self::ParseInt(e) => write!(f, "Parse int error: {e}"),
-            Self::Reqwest(e) => write!(f, "Reqwest error: {e}"),
-            Self::ParseDate(e) => write!(f, "Date parse error: {e}"),
-            Self::Plotting(e) => write!(f, "Plotting error: {e}"),
-            Self::Toml(e) => write!(f, "Toml error: {e}"),
-            Self::Ui => write!(f, "UI error"),
+        if let Some(file) = &self.context.file {
+            write!(f, "{file}: ")?;
+        }
+        match &self.kind {
+            ErrorKind::Config => write!(f, "reading config failed"),
+            ErrorKind::Download => write!(f, "download failed"),
+            ErrorKind::Decode => write!(f, "decoding failed"),
+            ErrorKind::Header(name) => write!(f, "header {name} malformed"),
+            ErrorKind::Move(san) => write!(f, "move {san} invalid"),
+            ErrorKind::Output => write!(f, "writing output failed"),
+            ErrorKind::Plotting => write!(f, "plotting failed"),
+            ErrorKind::Ui => write!(f, "user interface failed"),
+            ErrorKind::Lock => write!(f, "lock failed"),
         }?;
+        if let Some(game) = &self.context.game {
+            write!(f, " in game {game}")?;
+        }
+        if let Some(ply) = self.context.ply {
+            write!(f, " at ply {ply}")?;
+        }
         Ok(())
     }
 }
 
-fn comma_separated<T: Display>(v: &[T]) -> String {
-    v.iter().map(ToString::to_string).join(", ")
+impl Cause {
+    pub fn unexpected(value: &[u8]) -> Self {
+        Self::UnexpectedValue(String::from_utf8_lossy(value).to_string())
+    }
+}
+
+impl std::error::Error for Cause {}
+
+impl Display for Cause {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnexpectedValue(s) => write!(f, "unexpected value \"{s}\""),
+            Self::InvalidFilename(s) => write!(f, "invalid filename \"{s}\""),
+            Self::NoContentLength => write!(f, "no content length"),
+            Self::NotProcessing => write!(f, "file is not being processed"),
+            Self::Poisoned => write!(f, "mutex poisoned"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_header_error() {
+        let err = Error::header("UTCDate", Cause::UnexpectedValue("2019.13.45".to_string()))
+            .in_game("abc12345")
+            .in_file("2019-07");
+        assert_eq!(
+            err.to_string(),
+            "2019-07: header UTCDate malformed in game abc12345"
+        );
+        assert_eq!(
+            err.report(),
+            "2019-07: header UTCDate malformed in game abc12345: unexpected value \"2019.13.45\""
+        );
+    }
+
+    #[test]
+    fn test_context_keeps_innermost() {
+        let err = Error::invalid_move("Qxh7".to_string(), Cause::NoContentLength)
+            .at_ply(31)
+            .at_ply(12)
+            .in_file("2019-07")
+            .in_file("2020-01");
+        let expected = Context {
+            file: Some("2019-07".to_string()),
+            game: None,
+            ply: Some(31),
+        };
+        assert_eq!(err.context(), &expected);
+        assert_eq!(err.kind(), &ErrorKind::Move("Qxh7".to_string()));
+    }
 }
@@ -1,21 +1,41 @@
+mod analysis;
+mod annotated_pgn;
+mod census;
+mod detector;
+mod draw;
 mod enums;
+mod failure;
+mod forced_mate;
 mod game;
 mod game_data;
 mod game_player_data;
+mod mate_pattern;
+mod material;
 mod validator;
 
-pub use enums::MoveType;
+pub use analysis::Analysis;
+pub use census::{CensusReport, PositionCensus};
+pub use detector::{DetectorKind, RareMoveDetectors};
+pub use draw::DrawType;
+pub use enums::{Clock, GameResult, MoveType};
+pub use failure::{FailureReason, GameFailure};
+pub use forced_mate::ForcedMateSearch;
 pub use game::Game;
 pub use game_data::{GameData, RareMoveWithLink};
 pub use game_player_data::GamePlayerData;
-pub use validator::{FirstMove, Validator};
+pub use mate_pattern::{MatePattern, MatePatterns};
+pub use validator::{FirstMove, ParsedGame, Validator};
 
 #[cfg(test)]
 #[allow(clippy::panic_in_result_fn)]
 mod tests {
     extern crate test;
-    use enums::{CheckType, GameResult, Termination, TimeControl};
+    use std::str::FromStr;
+
+    use enums::{CheckType, Clock, GameResult, Termination, TimeControl};
     use pgn_reader::BufferedReader;
+    use shakmaty::{fen::Fen, san::San, CastlingMode, Chess, Position};
+    use validator::SkipReason;
 
     use super::*;
 
@@ -68,6 +88,111 @@ mod tests {
 
 "#;
 
+    const FROM_POSITION_GAME: &str = r#"[Event "Rated Blitz game"]
+[Site "https://lichess.org/abcdefgh"]
+[Result "0-1"]
+[UTCDate "2024.06.09"]
+[UTCTime "07:51:44"]
+[WhiteElo "1500"]
+[BlackElo "1500"]
+[TimeControl "180+0"]
+[Termination "Normal"]
+[SetUp "1"]
+[FEN "3r2k1/5ppp/8/8/8/8/P5PP/7K b - - 0 1"]
+
+1... Kf8 2. a3 Rd1# 0-1
+
+"#;
+
+    #[test]
+    fn test_parser_from_position() -> Result<()> {
+        let reader = BufferedReader::new(FROM_POSITION_GAME.as_bytes());
+        let mut validator = Validator::new();
+        let game = reader
+            .into_iter(&mut validator)
+            .next()
+            .ok_or("No game found")??
+            .into_game()
+            .ok_or("Game was not parsed")?;
+
+        let result = game.validate(&Analysis::default())?;
+
+        assert_eq!(
+            (result.from_position, result.half_moves, result.result),
+            (true, 3, GameResult::BlackWin)
+        );
+        // the missed Rd1# on the first move belongs to black, who moves first
+        assert_eq!(
+            (
+                result.black_player.missed_mates,
+                result.white_player.missed_mates
+            ),
+            (1, 0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_malformed_header() -> Result<()> {
+        let pgn = MINIMAL_GAME.replace(r#"[Result "1-0"]"#, r#"[Result "2-0"]"#);
+        let reader = BufferedReader::new(pgn.as_bytes());
+        let mut validator = Validator::new();
+        let parsed = reader
+            .into_iter(&mut validator)
+            .next()
+            .ok_or("No game found")??;
+
+        let ParsedGame::Malformed(failure) = parsed else {
+            return Err("Game was not malformed".into());
+        };
+        assert_eq!(failure.reason, FailureReason::BadResult);
+        assert_eq!(
+            failure.error.to_string(),
+            "header Result malformed in game UcZZx10k"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_mate_pattern() -> Result<()> {
+        let pgn = MINIMAL_GAME.replace(
+            "1. f3 e6 2. g4 Qh4 1-0",
+            "1. e4 e5 2. Nf3 Nc6 3. Bc4 Nd4 4. Nxe5 Qg5 5. Nxf7 Qxg2 6. Rf1 Qxe4+ 7. Be2 Nf3# 0-1",
+        );
+        let reader = BufferedReader::new(pgn.as_bytes());
+        let mut validator = Validator::new();
+        let game = reader
+            .into_iter(&mut validator)
+            .next()
+            .ok_or("No game found")??
+            .into_game()
+            .ok_or("Game was not parsed")?;
+        let result = game.validate(&Analysis::default())?;
+
+        assert_eq!(
+            result.mate_patterns.patterns().collect::<Vec<_>>(),
+            vec![MatePattern::Smothered]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_skipped_variant() -> Result<()> {
+        let pgn = MINIMAL_GAME.replace(r#"[Variant "Standard"]"#, r#"[Variant "Atomic"]"#);
+        let reader = BufferedReader::new(pgn.as_bytes());
+        let mut validator = Validator::new();
+        let parsed = reader
+            .into_iter(&mut validator)
+            .next()
+            .ok_or("No game found")??;
+
+        assert!(matches!(
+            parsed,
+            ParsedGame::Skipped(SkipReason::UnsupportedVariant)
+        ));
+        Ok(())
+    }
+
     #[test]
     fn test_parser_double_disambiguation() -> Result<()> {
         let reader = BufferedReader::new(DOUBLE_DISAMBIGUATION_GAME.as_bytes());
@@ -75,7 +200,9 @@ mod tests {
         let game = reader
             .into_iter(&mut validator)
             .next()
-            .ok_or("No game found")??;
+            .ok_or("No game found")??
+            .into_game()
+            .ok_or("Game was not parsed")?;
 
         let mut expected = GameData {
             white_player: GamePlayerData::default(),
@@ -85,43 +212,75 @@ mod tests {
             half_moves: 99,
             move_variations: 2701,
             game_link: *b"UcZZx10k",
+            clock: Clock {
+                base_seconds: 600,
+                increment_seconds: 0,
+            },
             time_control: TimeControl::RapidGame,
             termination: Termination::Normal,
+            mate_patterns: MatePatterns::default(),
+            draw_type: DrawType::None,
+            unclaimed_draw: DrawType::None,
+            unclaimed_draw_ply: 0,
+            max_queens: 2,
+            max_queens_ply: 0,
+            from_position: false,
         };
 
-        let expected_rare_moves = vec![
-            RareMoveWithLink {
-                game_link: "https://lichess.org/UcZZx10k".to_string(),
-                san: "Bc6xe4".to_string(),
-                ply: 92,
-                move_type: MoveType::DoubleDisambiguationCheckmate {
-                    was_played: false,
-                    is_capture: true,
-                    checkmate_type: CheckType::Normal,
-                },
-            },
-            RareMoveWithLink {
-                game_link: "https://lichess.org/UcZZx10k".to_string(),
-                san: "Bc6xe4".to_string(),
-                ply: 98,
-                move_type: MoveType::DoubleDisambiguationCheckmate {
-                    was_played: true,
-                    is_capture: true,
-                    checkmate_type: CheckType::Normal,
-                },
+        let result = game.validate(&Analysis::default())?;
+        let rare_moves = result.get_rare_moves();
+        let expected_move = |ply, was_played, played: &str, fen: &str| RareMoveWithLink {
+            game_link: "https://lichess.org/UcZZx10k".to_string(),
+            san: "Bc6xe4".to_string(),
+            ply,
+            move_type: MoveType::DoubleDisambiguationCheckmate {
+                was_played,
+                is_capture: true,
+                checkmate_type: CheckType::Normal,
             },
-        ];
+            played: played.to_string(),
+            // Bd5 at ply 92 does not mate, white still wins
+            played_mates: was_played,
+            won: true,
+            available_plies: 1,
+            white: "Questforrarestmove".to_string(),
+            white_elo: 1500,
+            black: "Questforraremove".to_string(),
+            black_elo: 1500,
+            date: chrono::NaiveDate::from_ymd_opt(2024, 6, 9).unwrap_or_default(),
+            clock: expected.clock,
+            fen: fen.to_string(),
+        };
+        let fens = rare_moves.iter().map(|rare_move| rare_move.fen.as_str());
+        let expected_rare_moves = [(92, false, "Bd5"), (98, true, "Bc6xe4")]
+            .into_iter()
+            .zip(fens)
+            .map(|((ply, was_played, played), fen)| expected_move(ply, was_played, played, fen))
+            .collect::<Vec<_>>();
 
-        let result = game.validate()?;
         // not testing for these here
         expected.white_player = result.white_player.clone();
         expected.black_player = result.black_player.clone();
+        expected.mate_patterns = result.mate_patterns;
 
         assert_eq!(result, expected);
-        assert_eq!(result.get_rare_moves(), expected_rare_moves);
+        assert_eq!(rare_moves.len(), 2);
+        assert_eq!(rare_moves, expected_rare_moves);
+        // the fen is enough to replay the mate without the game
+        assert!(rare_moves
+            .iter()
+            .map(mates_from_fen)
+            .all(|mates| mates.is_ok_and(|mates| mates)));
         Ok(())
     }
 
+    fn mates_from_fen(rare_move: &RareMoveWithLink) -> Result<bool> {
+        let fen: Fen = rare_move.fen.parse()?;
+        let position: Chess = fen.into_position(CastlingMode::Standard)?;
+        let m = San::from_str(&rare_move.san)?.to_move(&position)?;
+        Ok(position.play(&m)?.is_checkmate())
+    }
+
     #[bench]
     fn bench_parser_game_validate(b: &mut test::Bencher) {
         let reader = BufferedReader::new(DOUBLE_DISAMBIGUATION_GAME.as_bytes());
@@ -129,9 +288,14 @@ mod tests {
         let games = reader
             .into_iter(&mut validator)
             .flatten()
+            .filter_map(ParsedGame::into_game)
             .collect::<Vec<_>>();
+        let analysis = Analysis::default();
         b.iter(|| {
-            let results = games.clone().into_iter().map(super::game::Game::validate);
+            let results = games
+                .clone()
+                .into_iter()
+                .map(|game| game.validate(&analysis));
             assert_eq!(results.count(), 1);
         });
     }
@@ -154,9 +318,14 @@ mod tests {
         let games = reader
             .into_iter(&mut validator)
             .flatten()
+            .filter_map(ParsedGame::into_game)
             .collect::<Vec<_>>();
+        let analysis = Analysis::default();
         b.iter(|| {
-            let results = games.clone().into_iter().map(super::game::Game::validate);
+            let results = games
+                .clone()
+                .into_iter()
+                .map(|game| game.validate(&analysis));
             assert_eq!(results.count(), 1);
         });
     }
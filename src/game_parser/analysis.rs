@@ -0,0 +1,12 @@
+use super::{ForcedMateSearch, RareMoveDetectors};
+
+// what every replayed game is checked for
+#[derive(Debug, Default)]
+pub struct Analysis {
+    pub detectors: RareMoveDetectors,
+    pub forced_mates: Option<ForcedMateSearch>,
+    // the positions of every game are kept for the census of the run
+    pub census: bool,
+    // the pgn of a game with a rare move is kept, needs the headers from the validator
+    pub rare_pgn: bool,
+}
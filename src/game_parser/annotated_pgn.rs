@@ -0,0 +1,174 @@
+use std::fmt::Display;
+
+use itertools::Itertools;
+use shakmaty::{
+    san::{San, SanPlus},
+    Chess, Move, MoveList, Position,
+};
+
+use super::{game_player_data::RareMove, Game};
+
+// the mainline with a comment wherever a rare move was played or a mate was missed,
+// ready to be imported into a lichess study
+#[derive(Debug)]
+pub struct AnnotatedPgn<'a>(pub &'a Game);
+
+impl AnnotatedPgn<'_> {
+    fn rare_moves(&self) -> Vec<&RareMove> {
+        let data = &self.0.data;
+        data.white_player
+            .rare_checkmates
+            .iter()
+            .chain(&data.black_player.rare_checkmates)
+            .collect()
+    }
+
+    fn result(&self) -> &str {
+        self.0
+            .headers
+            .iter()
+            .find(|(key, _)| key == "Result")
+            .map_or("*", |(_, value)| value.as_str())
+    }
+}
+
+// "12." before white, "12..." when the game or a comment leaves black to move first
+fn move_number(ply: usize, after_comment: bool) -> Option<String> {
+    match ply % 2 {
+        0 => Some(format!("{}. ", ply / 2 + 1)),
+        _ if after_comment => Some(format!("{}... ", ply / 2 + 1)),
+        _ => None,
+    }
+}
+
+fn mates(position: &Chess, m: &Move) -> bool {
+    let mut position = position.clone();
+    position.play_unchecked(m);
+    position.is_checkmate()
+}
+
+fn comments(position: &Chess, m: &Move, ply: usize, rare_moves: &[&RareMove]) -> Vec<String> {
+    let at_ply = rare_moves
+        .iter()
+        .filter(|rare_move| usize::from(rare_move.ply) == ply)
+        .collect_vec();
+    let rare = at_ply.iter().map(|rare_move| {
+        if rare_move.move_type.was_played() {
+            format!("rare move: {}", rare_move.move_type.name())
+        } else {
+            format!("missed {}: {}", rare_move.move_type.name(), rare_move.san)
+        }
+    });
+    // another mate played is not a missed one
+    let other_moves = if mates(position, m) {
+        MoveList::new()
+    } else {
+        position.legal_moves()
+    };
+    let missed_mates = other_moves
+        .into_iter()
+        .filter(|other| other != m && mates(position, other))
+        .map(|other| San::from_move(position, &other).to_string())
+        .filter(|san| !at_ply.iter().any(|rare_move| &rare_move.san == san))
+        .map(|san| format!("missed mate: {san}#"));
+    rare.chain(missed_mates).collect()
+}
+
+fn to_comment(comments: &[String]) -> String {
+    if comments.is_empty() {
+        String::new()
+    } else {
+        format!("{{ {} }} ", comments.join("; "))
+    }
+}
+
+impl Display for AnnotatedPgn<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let game = self.0;
+        for (key, value) in &game.headers {
+            writeln!(f, "[{key} \"{value}\"]")?;
+        }
+        writeln!(f)?;
+
+        let rare_moves = self.rare_moves();
+        let mut position = game.start_position.clone();
+        let first_ply = usize::from(position.turn().is_black());
+        let mut after_comment = true;
+        for (ply, san) in (first_ply..).zip(&game.sans) {
+            let Ok(m) = san.to_move(&position) else {
+                break;
+            };
+            let comments = comments(&position, &m, ply, &rare_moves);
+            let number = move_number(ply, after_comment).unwrap_or_default();
+            let san_plus = SanPlus::from_move_and_play_unchecked(&mut position, &m);
+            write!(f, "{number}{san_plus} {}", to_comment(&comments))?;
+            after_comment = !comments.is_empty();
+        }
+        writeln!(f, "{}", self.result())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::panic_in_result_fn)]
+mod tests {
+    use pgn_reader::BufferedReader;
+
+    use crate::game_parser::{Analysis, ParsedGame, Validator};
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+    const PGN: &str = r#"[Event "Rated Blitz game"]
+[Site "https://lichess.org/abcd1234"]
+[Result "1-0"]
+[Variant "From Position"]
+[FEN "4rkr1/p3p1p1/8/8/8/8/P7/4K2R w K - 0 1"]
+[SetUp "1"]
+
+1. a3 a6 2. O-O# 1-0
+
+"#;
+
+    #[test]
+    fn test_annotated_pgn() -> Result<()> {
+        let mut validator = Validator::new().keeping_headers(true);
+        let game = BufferedReader::new(PGN.as_bytes())
+            .into_iter(&mut validator)
+            .next()
+            .ok_or("No game found")??
+            .into_game()
+            .ok_or("Game was not parsed")?;
+        let analysis = Analysis {
+            rare_pgn: true,
+            ..Analysis::default()
+        };
+
+        let pgn = game
+            .validate_with_pgn(&analysis)?
+            .rare_pgn
+            .ok_or("No pgn for a rare game")?;
+
+        assert!(pgn.starts_with("[Event \"Rated Blitz game\"]\n[Site"));
+        assert!(pgn.ends_with(
+            "\n\n1. a3 { missed castling: O-O; missed mate: Rf1# } 1... a6 2. O-O# \
+             { rare move: castling } 1-0\n"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_pgn_without_rare_moves() -> Result<()> {
+        let pgn = PGN.replace(" w K ", " w - ").replace("2. O-O#", "2. Rf1#");
+        let game = BufferedReader::new(pgn.as_bytes())
+            .into_iter(&mut Validator::new())
+            .flatten()
+            .find_map(ParsedGame::into_game)
+            .ok_or("Game was not parsed")?;
+        let analysis = Analysis {
+            rare_pgn: true,
+            ..Analysis::default()
+        };
+
+        assert_eq!(game.validate_with_pgn(&analysis)?.rare_pgn, None);
+        Ok(())
+    }
+}
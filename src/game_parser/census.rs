@@ -0,0 +1,457 @@
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fmt::Display,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+};
+
+use chrono::DateTime;
+
+use super::GameData;
+use crate::Result;
+
+// one odd multiplier per row of the sketch
+const ROW_SEEDS: [u64; 4] = [
+    0x9e37_79b9_7f4a_7c15,
+    0xc2b2_ae3d_27d4_eb4f,
+    0x1656_67b1_9e37_79f9,
+    0xd6e8_feb8_6659_fd93,
+];
+
+// count-min sketch, collisions can only make a position look more common than it is
+#[derive(Debug)]
+struct Sketch {
+    rows: Vec<Vec<AtomicU32>>,
+    bits: u32,
+}
+
+impl Sketch {
+    fn new(width: usize) -> Self {
+        let width = width.max(2).next_power_of_two();
+        Self {
+            rows: ROW_SEEDS
+                .iter()
+                .map(|_| (0..width).map(|_| AtomicU32::new(0)).collect())
+                .collect(),
+            bits: width.trailing_zeros(),
+        }
+    }
+
+    fn slots(&self, hash: u64) -> impl Iterator<Item = &AtomicU32> {
+        self.rows.iter().zip(ROW_SEEDS).map(move |(row, seed)| {
+            let index = hash.wrapping_mul(seed) >> (u64::BITS - self.bits);
+            &row[index as usize]
+        })
+    }
+
+    // the estimate including this occurrence
+    fn increment(&self, hash: u64) -> u32 {
+        self.slots(hash)
+            .map(|counter| counter.fetch_add(1, Ordering::Relaxed).saturating_add(1))
+            .min()
+            .unwrap_or_default()
+    }
+
+    fn estimate(&self, hash: u64) -> u32 {
+        self.slots(hash)
+            .map(|counter| counter.load(Ordering::Relaxed))
+            .min()
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FirstReached {
+    pub game_link: [u8; 8],
+    pub start_time: u32,
+    pub ply: u16,
+}
+
+#[derive(Debug, Default)]
+struct Candidates {
+    firsts: HashMap<u64, FirstReached>,
+    dropped: u64,
+    // while full, eviction is only tried again after this many more inserts
+    inserts_until_eviction: usize,
+}
+
+// how many games reached each position, and where the ones seen only once came from
+#[derive(Debug)]
+pub struct PositionCensus {
+    // ply index of black's reply to move from_move, only the positions after it count
+    min_ply: u16,
+    max_candidates: usize,
+    max_common: usize,
+    sketch: Sketch,
+    candidates: Mutex<Candidates>,
+    // estimated count and latest game of the most common positions
+    common: Mutex<HashMap<u64, (u32, FirstReached)>>,
+    // count a position needs to enter the full common table
+    common_floor: AtomicU32,
+}
+
+impl PositionCensus {
+    pub fn new(
+        from_move: u16,
+        sketch_width: usize,
+        max_candidates: usize,
+        max_common: usize,
+    ) -> Self {
+        Self {
+            min_ply: from_move.saturating_mul(2).saturating_sub(1),
+            max_candidates,
+            max_common,
+            sketch: Sketch::new(sketch_width),
+            candidates: Mutex::default(),
+            common: Mutex::default(),
+            common_floor: AtomicU32::new(1),
+        }
+    }
+
+    // positions are expected once per game, with the ply they were first reached after
+    pub fn add_game(
+        &self,
+        game_data: &GameData,
+        positions: impl Iterator<Item = (u64, u16)>,
+    ) -> Result<()> {
+        let mut new_positions = Vec::new();
+        let mut common_positions = Vec::new();
+        for (hash, ply) in positions.filter(|&(_, ply)| ply >= self.min_ply) {
+            let reached = FirstReached {
+                game_link: game_data.game_link,
+                start_time: game_data.start_time,
+                ply,
+            };
+            match self.sketch.increment(hash) {
+                1 => new_positions.push((hash, reached)),
+                count if count > self.common_floor.load(Ordering::Relaxed) => {
+                    common_positions.push((hash, count, reached));
+                }
+                _ => {}
+            }
+        }
+        self.add_new(new_positions)?;
+        self.add_common(common_positions)
+    }
+
+    fn add_new(&self, new_positions: Vec<(u64, FirstReached)>) -> Result<()> {
+        if new_positions.is_empty() {
+            return Ok(());
+        }
+        let mut candidates = self.candidates.lock()?;
+        for (hash, first) in new_positions {
+            self.insert(&mut candidates, hash, first);
+        }
+        drop(candidates);
+        Ok(())
+    }
+
+    fn add_common(&self, common_positions: Vec<(u64, u32, FirstReached)>) -> Result<()> {
+        if common_positions.is_empty() || self.max_common == 0 {
+            return Ok(());
+        }
+        let mut common = self.common.lock()?;
+        for (hash, count, reached) in common_positions {
+            self.count(&mut common, hash, count, reached);
+        }
+        drop(common);
+        Ok(())
+    }
+
+    // keeps the max_common positions with the highest estimates
+    fn count(
+        &self,
+        common: &mut HashMap<u64, (u32, FirstReached)>,
+        hash: u64,
+        count: u32,
+        reached: FirstReached,
+    ) {
+        if common.len() >= self.max_common && !common.contains_key(&hash) {
+            let weakest = common
+                .iter()
+                .min_by_key(|(_, &(listed, _))| listed)
+                .map(|(&weakest, &(weakest_count, _))| (weakest, weakest_count));
+            match weakest {
+                Some((weakest, weakest_count)) if weakest_count < count => {
+                    common.remove(&weakest);
+                }
+                _ => return,
+            }
+        }
+        common.insert(hash, (count, reached));
+        if common.len() >= self.max_common {
+            let floor = common.values().map(|&(listed, _)| listed).min();
+            self.common_floor
+                .store(floor.unwrap_or_default(), Ordering::Relaxed);
+        }
+    }
+
+    pub fn report(&self) -> Result<CensusReport> {
+        let mut candidates = self.candidates.lock()?;
+        self.evict(&mut candidates);
+        let mut positions = candidates
+            .firsts
+            .iter()
+            .map(|(&hash, &first)| UniquePosition { hash, first })
+            .collect::<Vec<_>>();
+        positions.sort_unstable_by_key(|position| (position.first.start_time, position.hash));
+        let dropped = candidates.dropped;
+        drop(candidates);
+        Ok(CensusReport {
+            positions,
+            dropped,
+            common: self.common_positions()?,
+        })
+    }
+
+    // most common first, with the current estimates
+    fn common_positions(&self) -> Result<Vec<CommonPosition>> {
+        let mut positions = self
+            .common
+            .lock()?
+            .iter()
+            .map(|(&hash, &(_, reached))| CommonPosition {
+                hash,
+                count: self.sketch.estimate(hash),
+                reached,
+            })
+            .collect::<Vec<_>>();
+        positions.sort_unstable_by_key(|position| (Reverse(position.count), position.hash));
+        Ok(positions)
+    }
+
+    fn insert(&self, candidates: &mut Candidates, hash: u64, first: FirstReached) {
+        if candidates.firsts.len() >= self.max_candidates {
+            self.evict_or_wait(candidates);
+        }
+        if candidates.firsts.len() < self.max_candidates {
+            candidates.firsts.insert(hash, first);
+        } else {
+            candidates.dropped += 1;
+        }
+    }
+
+    // scanning the full table on every insert would stall, so a failed eviction waits
+    // until an eighth of the table has been offered again
+    fn evict_or_wait(&self, candidates: &mut Candidates) {
+        if candidates.inserts_until_eviction > 0 {
+            candidates.inserts_until_eviction -= 1;
+            return;
+        }
+        self.evict(candidates);
+        if candidates.firsts.len() >= self.max_candidates {
+            candidates.inserts_until_eviction = self.max_candidates / 8;
+        }
+    }
+
+    // forgets the positions another game has reached since
+    fn evict(&self, candidates: &mut Candidates) {
+        candidates
+            .firsts
+            .retain(|&hash, _| self.sketch.estimate(hash) <= 1);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UniquePosition {
+    pub hash: u64,
+    pub first: FirstReached,
+}
+
+// hash,ply,date,game_link
+impl Display for UniquePosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let date = DateTime::from_timestamp(i64::from(self.first.start_time), 0)
+            .map(|datetime| datetime.date_naive())
+            .unwrap_or_default();
+        write!(
+            f,
+            "{:016x},{},{date},https://lichess.org/{}#{}",
+            self.hash,
+            self.first.ply,
+            String::from_utf8_lossy(&self.first.game_link),
+            self.first.ply + 1
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommonPosition {
+    pub hash: u64,
+    pub count: u32,
+    // the latest game counted when the position entered the table
+    pub reached: FirstReached,
+}
+
+// hash,count,game_link
+impl Display for CommonPosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:016x},{},https://lichess.org/{}#{}",
+            self.hash,
+            self.count,
+            String::from_utf8_lossy(&self.reached.game_link),
+            self.reached.ply + 1
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CensusReport {
+    pub positions: Vec<UniquePosition>,
+    // positions that found the candidate table full, they may be missing from the report
+    pub dropped: u64,
+    pub common: Vec<CommonPosition>,
+}
+
+impl CensusReport {
+    pub fn common(&self) -> CommonPositions<'_> {
+        CommonPositions(&self.common)
+    }
+}
+
+#[derive(Debug)]
+pub struct CommonPositions<'a>(&'a [CommonPosition]);
+
+impl Display for CommonPositions<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0
+            .iter()
+            .try_for_each(|position| writeln!(f, "{position}"))
+    }
+}
+
+impl Display for CensusReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.dropped > 0 {
+            writeln!(
+                f,
+                "# {} positions dropped, the table was full",
+                self.dropped
+            )?;
+        }
+        self.positions
+            .iter()
+            .try_for_each(|position| writeln!(f, "{position}"))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::panic_in_result_fn)]
+mod tests {
+    use super::*;
+
+    fn game(game_link: [u8; 8], start_time: u32) -> GameData {
+        GameData {
+            start_time,
+            game_link,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_census_reports_positions_of_one_game() -> Result<()> {
+        let census = PositionCensus::new(1, 1 << 10, 100, 10);
+        census.add_game(
+            &game(*b"aaaaaaaa", 100),
+            [(1, 0), (2, 1), (3, 2)].into_iter(),
+        )?;
+        census.add_game(
+            &game(*b"bbbbbbbb", 200),
+            [(1, 0), (2, 1), (4, 2)].into_iter(),
+        )?;
+        let report = census.report()?;
+        let hashes = report
+            .positions
+            .iter()
+            .map(|position| position.hash)
+            .collect::<Vec<_>>();
+
+        // ply 0 is white's first move, move one is complete after ply 1
+        assert_eq!(hashes, vec![3, 4]);
+        assert_eq!(report.positions[0].first.game_link, *b"aaaaaaaa");
+        assert_eq!(
+            report.to_string().lines().next(),
+            Some("0000000000000003,2,1970-01-01,https://lichess.org/aaaaaaaa#3")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_census_is_bounded() -> Result<()> {
+        let census = PositionCensus::new(0, 1 << 10, 2, 10);
+        census.add_game(&game(*b"aaaaaaaa", 0), [(1, 0), (2, 1)].into_iter())?;
+        census.add_game(&game(*b"bbbbbbbb", 0), [(1, 0), (3, 1)].into_iter())?;
+        census.add_game(&game(*b"cccccccc", 0), std::iter::once((4, 0)))?;
+        let report = census.report()?;
+
+        // 1 was evicted once a second game reached it, making room for 3
+        assert_eq!(report.positions.len(), 2);
+        assert_eq!(report.dropped, 1);
+        assert!(report.to_string().starts_with("# 1 positions dropped"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_census_retries_eviction() -> Result<()> {
+        let census = PositionCensus::new(0, 1 << 10, 8, 10);
+        census.add_game(&game(*b"aaaaaaaa", 0), (0..8).map(|hash| (hash, 0)))?;
+        // nothing to evict yet, the next eighth of the table is dropped without a scan
+        census.add_game(&game(*b"bbbbbbbb", 0), std::iter::once((8, 0)))?;
+        census.add_game(&game(*b"bbbbbbbb", 0), (0..4).map(|hash| (hash, 0)))?;
+        census.add_game(&game(*b"cccccccc", 0), [(9, 0), (10, 0)].into_iter())?;
+        let report = census.report()?;
+
+        // 9 still waited, 10 evicted the positions the second game reached again
+        assert_eq!(report.dropped, 2);
+        assert!(report.positions.iter().any(|position| position.hash == 10));
+        Ok(())
+    }
+
+    #[test]
+    fn test_census_reports_a_poisoned_lock() {
+        let census = PositionCensus::new(0, 1 << 10, 100, 10);
+        std::thread::scope(|scope| {
+            let worker = scope.spawn(|| {
+                let _candidates = census.candidates.lock();
+                std::panic::resume_unwind(Box::new("worker panicked holding the candidates"));
+            });
+            assert!(worker.join().is_err());
+        });
+
+        assert!(census
+            .add_game(&game(*b"aaaaaaaa", 0), std::iter::once((1, 0)))
+            .is_err());
+        assert!(census.report().is_err());
+    }
+
+    #[test]
+    fn test_census_counts_common_positions() -> Result<()> {
+        let census = PositionCensus::new(0, 1 << 10, 100, 2);
+        for (game_link, games) in [(b"aaaaaaaa", 5), (b"bbbbbbbb", 3), (b"cccccccc", 4)] {
+            for _ in 0..games {
+                census.add_game(
+                    &game(*game_link, 0),
+                    std::iter::once((game_link[0].into(), 0)),
+                )?;
+            }
+        }
+        let report = census.report()?;
+        let counts = report
+            .common
+            .iter()
+            .map(|position| (position.hash, position.count))
+            .collect::<Vec<_>>();
+
+        assert_eq!(counts, [(0x61, 5), (0x63, 4)]);
+        assert_eq!(
+            report.common().to_string().lines().next(),
+            Some("0000000000000061,5,https://lichess.org/aaaaaaaa#1")
+        );
+        Ok(())
+    }
+}
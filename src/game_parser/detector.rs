@@ -0,0 +1,344 @@
+use std::fmt::Debug;
+
+use itertools::Itertools;
+use serde::Deserialize;
+use shakmaty::{attacks, san::San, Chess, Move, Position, Role, Square};
+
+use crate::util::is_double_disambiguation;
+
+use super::{
+    enums::{CheckType, MoveType},
+    game_player_data::RareMove,
+};
+
+pub trait RareMoveDetector: Debug + Send + Sync {
+    // called for every checkmating move, played or missed
+    fn detect(&self, position: &Chess, m: &Move, ply: usize, was_played: bool) -> Vec<RareMove>;
+}
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum DetectorKind {
+    DoubleDisambiguation,
+    KingCapture,
+    Castling,
+    Underpromotion,
+    EnPassant,
+}
+
+impl DetectorKind {
+    pub const ALL: [Self; 5] = [
+        Self::DoubleDisambiguation,
+        Self::KingCapture,
+        Self::Castling,
+        Self::Underpromotion,
+        Self::EnPassant,
+    ];
+
+    pub fn all() -> Vec<Self> {
+        Self::ALL.to_vec()
+    }
+
+    fn detector(self) -> Box<dyn RareMoveDetector> {
+        match self {
+            Self::DoubleDisambiguation => Box::new(DoubleDisambiguationDetector),
+            Self::KingCapture => Box::new(KingCaptureDetector),
+            Self::Castling => Box::new(CastlingDetector),
+            Self::Underpromotion => Box::new(UnderpromotionDetector),
+            Self::EnPassant => Box::new(EnPassantDetector),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RareMoveDetectors(Vec<Box<dyn RareMoveDetector>>);
+
+impl RareMoveDetectors {
+    pub fn new(kinds: &[DetectorKind]) -> Self {
+        Self(kinds.iter().unique().map(|kind| kind.detector()).collect())
+    }
+
+    pub fn detect(
+        &self,
+        position: &Chess,
+        m: &Move,
+        ply: usize,
+        was_played: bool,
+    ) -> Vec<RareMove> {
+        self.0
+            .iter()
+            .flat_map(|detector| detector.detect(position, m, ply, was_played))
+            .collect()
+    }
+}
+
+impl Default for RareMoveDetectors {
+    fn default() -> Self {
+        Self::new(&DetectorKind::ALL)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct DoubleDisambiguationDetector;
+
+impl RareMoveDetector for DoubleDisambiguationDetector {
+    fn detect(&self, position: &Chess, m: &Move, ply: usize, was_played: bool) -> Vec<RareMove> {
+        if m.role() == Role::Pawn || m.role() == Role::King {
+            return Vec::new();
+        }
+        let san = San::from_move(position, m);
+        if !is_double_disambiguation(&san) {
+            return Vec::new();
+        }
+        let move_type = MoveType::DoubleDisambiguationCheckmate {
+            is_capture: m.is_capture(),
+            was_played,
+            checkmate_type: get_check_type(position, m),
+        };
+        vec![RareMove::new(position, m, ply, move_type)]
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct KingCaptureDetector;
+
+impl RareMoveDetector for KingCaptureDetector {
+    fn detect(&self, position: &Chess, m: &Move, ply: usize, was_played: bool) -> Vec<RareMove> {
+        if m.role() != Role::King || !m.is_capture() {
+            return Vec::new();
+        }
+        let move_type = MoveType::KingCheckmate {
+            is_capture: true,
+            was_played,
+        };
+        vec![RareMove::new(position, m, ply, move_type)]
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct CastlingDetector;
+
+impl RareMoveDetector for CastlingDetector {
+    fn detect(&self, position: &Chess, m: &Move, ply: usize, was_played: bool) -> Vec<RareMove> {
+        m.castling_side()
+            .map(|side| MoveType::CastlingCheckmate { was_played, side })
+            .map(|move_type| RareMove::new(position, m, ply, move_type))
+            .into_iter()
+            .collect()
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct UnderpromotionDetector;
+
+impl RareMoveDetector for UnderpromotionDetector {
+    fn detect(&self, position: &Chess, m: &Move, ply: usize, was_played: bool) -> Vec<RareMove> {
+        m.promotion()
+            .filter(|&role| role != Role::Queen)
+            .map(|role| MoveType::UnderpromotionCheckmate {
+                was_played,
+                is_capture: m.is_capture(),
+                role,
+            })
+            .map(|move_type| RareMove::new(position, m, ply, move_type))
+            .into_iter()
+            .collect()
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct EnPassantDetector;
+
+impl RareMoveDetector for EnPassantDetector {
+    fn detect(&self, position: &Chess, m: &Move, ply: usize, was_played: bool) -> Vec<RareMove> {
+        if !m.is_en_passant() {
+            return Vec::new();
+        }
+        let move_type = MoveType::EnPassantCheckmate {
+            was_played,
+            discovered: is_discovered_through_captured_pawn(position, m),
+        };
+        vec![RareMove::new(position, m, ply, move_type)]
+    }
+}
+
+// a line piece checks through the square of the pawn that was taken en passant
+fn is_discovered_through_captured_pawn(position: &Chess, m: &Move) -> bool {
+    let Move::EnPassant { from, to } = *m else {
+        return false;
+    };
+    let captured = Square::from_coords(to.file(), from.rank());
+    let mut position = position.clone();
+    position.play_unchecked(m);
+    let king = position.board().king_of(position.turn());
+    position
+        .checkers()
+        .into_iter()
+        .any(|checker| king.is_some_and(|king| attacks::between(checker, king).contains(captured)))
+}
+
+fn get_check_type(position: &Chess, m: &Move) -> CheckType {
+    let sq = m.to();
+    let mut position = position.clone();
+    position.play_unchecked(m);
+    let checkers = position.checkers();
+    match (checkers.contains(sq), checkers.count() > 1) {
+        (true, true) => CheckType::Double,
+        (true, false) => CheckType::Normal,
+        (false, _) => CheckType::Discovered,
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::panic_in_result_fn)]
+mod tests {
+    use std::str::FromStr;
+
+    use rstest::rstest;
+    use shakmaty::{
+        fen::Fen,
+        zobrist::{Zobrist64, ZobristHash},
+        CastlingMode, CastlingSide, EnPassantMode,
+    };
+
+    use super::*;
+
+    type Error = Box<dyn std::error::Error>;
+    type Result<T> = std::result::Result<T, Error>;
+
+    fn to_position_and_move(fen: &str, san: &str) -> Result<(Chess, Move)> {
+        let fen: Fen = fen.parse()?;
+        let position: Chess = fen.into_position(CastlingMode::Standard)?;
+        let m = San::from_str(san)?.to_move(&position)?;
+        Ok((position, m))
+    }
+
+    #[test]
+    fn test_is_discovered_mate() -> Result<()> {
+        let (position, m) =
+            to_position_and_move("N2NQN1k/7p/7B/7R/5N2/7P/6B1/6K1 w - -", "Nf8e6#")?;
+        assert_eq!(get_check_type(&position, &m), CheckType::Discovered);
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_not_discovered_mate() -> Result<()> {
+        let (position, m) = to_position_and_move("3NQN1k/4N1Np/7B/5B1R/7P/8/8/6K1 w - -", "Nf7#")?;
+        assert_eq!(get_check_type(&position, &m), CheckType::Normal);
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_discovered_double_mate() -> Result<()> {
+        let (position, m) = to_position_and_move("3NQN1k/7p/4N2B/3N3R/7P/8/6B1/6K1 w - -", "Ng6#")?;
+        assert_eq!(get_check_type(&position, &m), CheckType::Double);
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_normal_double_disambiguation_mate() -> Result<()> {
+        let (position, m) = to_position_and_move("1k6/2N1N3/1K6/NNN1N3/8/8/8/8 w - -", "Ne5c6#")?;
+        assert_eq!(get_check_type(&position, &m), CheckType::Normal);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(
+        "4rkr1/4p1p1/8/8/8/8/8/4K2R w K - 0 1",
+        "O-O",
+        CastlingSide::KingSide,
+        "OO   "
+    )]
+    #[case(
+        "2rkr3/2p1p3/8/8/8/8/8/R3K3 w Q - 0 1",
+        "O-O-O",
+        CastlingSide::QueenSide,
+        "OOO  "
+    )]
+    fn test_castling_checkmate(
+        #[case] fen: &str,
+        #[case] san: &str,
+        #[case] side: CastlingSide,
+        #[case] display: &str,
+    ) -> Result<()> {
+        let (position, m) = to_position_and_move(fen, san)?;
+        let rare_moves = CastlingDetector.detect(&position, &m, 20, true);
+        let expected = RareMove {
+            san: san.to_string(),
+            ply: 20,
+            move_type: MoveType::CastlingCheckmate {
+                was_played: true,
+                side,
+            },
+            fen: fen.to_string(),
+            played: String::new(),
+            played_mates: false,
+            won: false,
+            position_hash: position.zobrist_hash::<Zobrist64>(EnPassantMode::Legal).0,
+            available_plies: 1,
+        };
+
+        assert!(position.play(&m)?.is_checkmate());
+        assert_eq!(rare_moves, vec![expected]);
+        assert_eq!(rare_moves[0].move_type.to_string(), display);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case("6br/5Ppk/7p/8/8/8/8/K7 w - - 0 1", "f8=N", "=N   ")]
+    #[case("1r5k/2P3pp/8/8/8/8/8/K7 w - - 0 1", "cxb8=R", "=Rx ?")]
+    fn test_underpromotion_checkmate(
+        #[case] fen: &str,
+        #[case] san: &str,
+        #[case] display: &str,
+    ) -> Result<()> {
+        let (position, m) = to_position_and_move(fen, san)?;
+        let was_played = !display.ends_with('?');
+        let rare_moves = UnderpromotionDetector.detect(&position, &m, 40, was_played);
+
+        assert!(position.play(&m)?.is_checkmate());
+        assert_eq!(rare_moves.len(), 1);
+        assert_eq!(rare_moves[0].san, san);
+        assert_eq!(rare_moves[0].move_type.to_string(), display);
+        Ok(())
+    }
+
+    #[test]
+    fn test_en_passant_checkmate() -> Result<()> {
+        let (position, m) = to_position_and_move("8/4N3/7p/R2pP2k/6pp/8/8/K7 w - d6 0 1", "exd6")?;
+        let rare_moves = EnPassantDetector.detect(&position, &m, 30, false);
+        let expected = RareMove {
+            san: "exd6".to_string(),
+            ply: 30,
+            move_type: MoveType::EnPassantCheckmate {
+                was_played: false,
+                discovered: true,
+            },
+            fen: "8/4N3/7p/R2pP2k/6pp/8/8/K7 w - d6 0 1".to_string(),
+            played: String::new(),
+            played_mates: false,
+            won: false,
+            position_hash: position.zobrist_hash::<Zobrist64>(EnPassantMode::Legal).0,
+            available_plies: 1,
+        };
+
+        assert!(position.play(&m)?.is_checkmate());
+        assert_eq!(rare_moves, vec![expected]);
+        assert_eq!(rare_moves[0].move_type.to_string(), "EP D?");
+        Ok(())
+    }
+
+    #[test]
+    fn test_enabled_detectors() -> Result<()> {
+        let (position, m) = to_position_and_move("1k6/2N1N3/1K6/NNN1N3/8/8/8/8 w - -", "Ne5c6#")?;
+        let king_capture_only = RareMoveDetectors::new(&[DetectorKind::KingCapture]);
+        assert!(king_capture_only.detect(&position, &m, 0, true).is_empty());
+
+        let duplicated = RareMoveDetectors::new(&[
+            DetectorKind::DoubleDisambiguation,
+            DetectorKind::DoubleDisambiguation,
+        ]);
+        assert_eq!(duplicated.detect(&position, &m, 0, true).len(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,207 @@
+use std::{collections::HashMap, fmt::Display};
+
+use shakmaty::{
+    zobrist::{Zobrist64, ZobristHash},
+    Chess, EnPassantMode, Position,
+};
+
+use super::{
+    enums::{GameResult, Termination},
+    GameData,
+};
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum DrawType {
+    #[default]
+    None = 0,
+    Stalemate = 1,
+    InsufficientMaterial = 2,
+    // the flagged player's opponent had no mating material left
+    Timeout = 3,
+    ThreefoldRepetition = 4,
+    FiftyMoves = 5,
+    Agreement = 6,
+}
+
+impl Display for DrawType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::None => "none",
+            Self::Stalemate => "stalemate",
+            Self::InsufficientMaterial => "insufficient_material",
+            Self::Timeout => "timeout",
+            Self::ThreefoldRepetition => "threefold_repetition",
+            Self::FiftyMoves => "fifty_moves",
+            Self::Agreement => "agreement",
+        };
+        write!(f, "{s}")
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Occurrence {
+    count: u8,
+    first_ply: u16,
+}
+
+// follows the positions of a game as it is replayed
+#[derive(Debug, Default)]
+pub struct DrawTracker {
+    positions: HashMap<Zobrist64, Occurrence>,
+    last_repetitions: u8,
+    first_claim: Option<(DrawType, u16)>,
+}
+
+impl DrawTracker {
+    pub fn new(start_position: &Chess) -> Self {
+        let mut tracker = Self::default();
+        tracker.last_repetitions = tracker.record(start_position, 0);
+        tracker
+    }
+
+    // called with the position after the move of the given ply
+    pub fn update(&mut self, position: &Chess, ply: usize) {
+        self.last_repetitions = self.record(position, ply);
+        if self.first_claim.is_some() {
+            return;
+        }
+        self.first_claim = self.claimable(position).map(|draw| (draw, ply as u16));
+    }
+
+    pub fn finish(&self, game_data: &mut GameData, position: &Chess) {
+        match game_data.result {
+            GameResult::Draw => {
+                game_data.draw_type = self.classify(position, game_data.termination);
+            }
+            GameResult::WhiteWin | GameResult::BlackWin => {
+                if let Some((draw, ply)) = self.first_claim {
+                    game_data.unclaimed_draw = draw;
+                    game_data.unclaimed_draw_ply = ply;
+                }
+            }
+            GameResult::Unfinished => {}
+        }
+    }
+
+    // every distinct position of the game with the ply it was first reached after
+    pub fn positions(&self) -> impl Iterator<Item = (u64, u16)> + '_ {
+        self.positions
+            .iter()
+            .map(|(hash, occurrence)| (hash.0, occurrence.first_ply))
+    }
+
+    fn record(&mut self, position: &Chess, ply: usize) -> u8 {
+        let hash = position.zobrist_hash::<Zobrist64>(EnPassantMode::Legal);
+        let occurrence = self.positions.entry(hash).or_insert(Occurrence {
+            count: 0,
+            first_ply: ply as u16,
+        });
+        occurrence.count = occurrence.count.saturating_add(1);
+        occurrence.count
+    }
+
+    fn claimable(&self, position: &Chess) -> Option<DrawType> {
+        if self.last_repetitions >= 3 {
+            Some(DrawType::ThreefoldRepetition)
+        } else if position.halfmoves() >= 100 {
+            Some(DrawType::FiftyMoves)
+        } else {
+            None
+        }
+    }
+
+    fn classify(&self, position: &Chess, termination: Termination) -> DrawType {
+        if position.is_stalemate() {
+            DrawType::Stalemate
+        } else if position.is_insufficient_material() {
+            DrawType::InsufficientMaterial
+        } else if termination == Termination::TimeForfeit {
+            DrawType::Timeout
+        } else {
+            self.claimable(position).unwrap_or(DrawType::Agreement)
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::panic_in_result_fn)]
+mod tests {
+    use std::str::FromStr;
+
+    use rstest::rstest;
+    use shakmaty::{fen::Fen, san::San, CastlingMode};
+
+    use super::*;
+
+    type Error = Box<dyn std::error::Error>;
+    type Result<T> = std::result::Result<T, Error>;
+
+    const KNIGHT_SHUFFLE: &str = "Nf3 Nf6 Ng1 Ng8 Nf3 Nf6 Ng1 Ng8";
+
+    fn replay(fen: &str, sans: &str, result: GameResult) -> Result<GameData> {
+        let fen: Fen = fen.parse()?;
+        let mut position: Chess = fen.into_position(CastlingMode::Standard)?;
+        let mut tracker = DrawTracker::new(&position);
+        for (ply, san) in sans.split_whitespace().enumerate() {
+            let m = San::from_str(san)?.to_move(&position)?;
+            position.play_unchecked(&m);
+            tracker.update(&position, ply);
+        }
+        let mut game_data = GameData {
+            result,
+            ..Default::default()
+        };
+        tracker.finish(&mut game_data, &position);
+        Ok(game_data)
+    }
+
+    #[rstest]
+    #[case(
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        KNIGHT_SHUFFLE,
+        DrawType::ThreefoldRepetition
+    )]
+    #[case("k7/8/8/8/8/8/8/1Q5K w - - 0 1", "Qb6", DrawType::Stalemate)]
+    #[case(
+        "k7/8/8/8/8/8/1r6/3N3K w - - 0 1",
+        "Nxb2",
+        DrawType::InsufficientMaterial
+    )]
+    #[case("k7/8/8/8/8/8/8/1Q5K w - - 99 80", "Kg1", DrawType::FiftyMoves)]
+    #[case("k7/8/8/8/8/8/8/1Q5K w - - 0 1", "Kg1", DrawType::Agreement)]
+    fn test_classify_draw(
+        #[case] fen: &str,
+        #[case] sans: &str,
+        #[case] expected: DrawType,
+    ) -> Result<()> {
+        let game_data = replay(fen, sans, GameResult::Draw)?;
+
+        assert_eq!(game_data.draw_type, expected);
+        assert_eq!(game_data.unclaimed_draw, DrawType::None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unclaimed_draw() -> Result<()> {
+        let sans = format!("{KNIGHT_SHUFFLE} e4");
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let game_data = replay(fen, &sans, GameResult::WhiteWin)?;
+
+        assert_eq!(game_data.draw_type, DrawType::None);
+        assert_eq!(game_data.unclaimed_draw, DrawType::ThreefoldRepetition);
+        assert_eq!(game_data.unclaimed_draw_ply, 7);
+        Ok(())
+    }
+
+    #[test]
+    fn test_repetition_count_saturates() -> Result<()> {
+        // the start position comes back 260 times, past what a u8 holds
+        let sans = [KNIGHT_SHUFFLE; 130].join(" ");
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let game_data = replay(fen, &sans, GameResult::Draw)?;
+
+        assert_eq!(game_data.draw_type, DrawType::ThreefoldRepetition);
+        Ok(())
+    }
+}
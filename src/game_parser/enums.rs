@@ -1,6 +1,12 @@
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+use serde_with::{DeserializeFromStr, SerializeDisplay};
+use shakmaty::{CastlingSide, Role};
+
+use crate::error::{Cause, Source};
+
+// serialized as the five character code of the catalogue, like "DDxD?"
+#[derive(Debug, Clone, PartialEq, Eq, SerializeDisplay, DeserializeFromStr)]
 pub enum MoveType {
     KingCheckmate {
         was_played: bool,
@@ -11,6 +17,28 @@ pub enum MoveType {
         is_capture: bool,
         checkmate_type: CheckType,
     },
+    CastlingCheckmate {
+        was_played: bool,
+        side: CastlingSide,
+    },
+    UnderpromotionCheckmate {
+        was_played: bool,
+        is_capture: bool,
+        role: Role,
+    },
+    EnPassantCheckmate {
+        was_played: bool,
+        discovered: bool,
+    },
+    // only logged for the final move of a game
+    Stalemate {
+        is_capture: bool,
+        promotion: Option<Role>,
+    },
+    // first move of a forced mate that was not played
+    ForcedMate {
+        depth: u8,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -21,6 +49,34 @@ pub enum CheckType {
 }
 
 impl MoveType {
+    pub const fn is_checkmate(&self) -> bool {
+        !matches!(self, Self::Stalemate { .. } | Self::ForcedMate { .. })
+    }
+
+    pub const fn was_played(&self) -> bool {
+        match self {
+            Self::KingCheckmate { was_played, .. }
+            | Self::DoubleDisambiguationCheckmate { was_played, .. }
+            | Self::CastlingCheckmate { was_played, .. }
+            | Self::UnderpromotionCheckmate { was_played, .. }
+            | Self::EnPassantCheckmate { was_played, .. } => *was_played,
+            Self::Stalemate { .. } => true,
+            Self::ForcedMate { .. } => false,
+        }
+    }
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::KingCheckmate { .. } => "king",
+            Self::DoubleDisambiguationCheckmate { .. } => "double_disambiguation",
+            Self::CastlingCheckmate { .. } => "castling",
+            Self::UnderpromotionCheckmate { .. } => "underpromotion",
+            Self::EnPassantCheckmate { .. } => "en_passant",
+            Self::Stalemate { .. } => "stalemate",
+            Self::ForcedMate { .. } => "forced_mate",
+        }
+    }
+
     fn format_king_checkmate(is_capture: bool, was_played: bool) -> String {
         format!(
             "K {} {}",
@@ -45,6 +101,46 @@ impl MoveType {
             if was_played { ' ' } else { '?' },
         )
     }
+
+    fn format_castling_checkmate(side: CastlingSide, was_played: bool) -> String {
+        format!(
+            "{:4}{}",
+            match side {
+                CastlingSide::KingSide => "OO",
+                CastlingSide::QueenSide => "OOO",
+            },
+            if was_played { ' ' } else { '?' },
+        )
+    }
+
+    fn format_underpromotion_checkmate(role: Role, is_capture: bool, was_played: bool) -> String {
+        format!(
+            "={}{} {}",
+            role.upper_char(),
+            if is_capture { 'x' } else { ' ' },
+            if was_played { ' ' } else { '?' },
+        )
+    }
+
+    fn format_en_passant_checkmate(discovered: bool, was_played: bool) -> String {
+        format!(
+            "EP {}{}",
+            if discovered { 'D' } else { ' ' },
+            if was_played { ' ' } else { '?' },
+        )
+    }
+
+    fn format_stalemate(is_capture: bool, promotion: Option<Role>) -> String {
+        format!(
+            "SM{}{} ",
+            if is_capture { 'x' } else { ' ' },
+            promotion.map_or(' ', Role::upper_char),
+        )
+    }
+
+    fn format_forced_mate(depth: u8) -> String {
+        format!("M{depth:<3}?")
+    }
 }
 
 impl Display for MoveType {
@@ -63,11 +159,96 @@ impl Display for MoveType {
                 *was_played,
                 checkmate_type,
             ),
+            Self::CastlingCheckmate { was_played, side } => {
+                Self::format_castling_checkmate(*side, *was_played)
+            }
+            Self::UnderpromotionCheckmate {
+                was_played,
+                is_capture,
+                role,
+            } => Self::format_underpromotion_checkmate(*role, *is_capture, *was_played),
+            Self::EnPassantCheckmate {
+                was_played,
+                discovered,
+            } => Self::format_en_passant_checkmate(*discovered, *was_played),
+            Self::Stalemate {
+                is_capture,
+                promotion,
+            } => Self::format_stalemate(*is_capture, *promotion),
+            Self::ForcedMate { depth } => Self::format_forced_mate(*depth),
         };
         write!(f, "{s}")
     }
 }
 
+// reads back the codes written by Display, trailing spaces may be trimmed
+impl FromStr for MoveType {
+    type Err = Source;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let padded = format!("{s:<5}");
+        let code = padded.as_bytes();
+        let (was_played, is_capture) = (code.get(4) != Some(&b'?'), code.get(2) == Some(&b'x'));
+        let move_type = match code {
+            [b'K', b' ', ..] => Some(Self::KingCheckmate {
+                was_played,
+                is_capture,
+            }),
+            [b'D', b'D', ..] => Self::parse_double_disambiguation(code, was_played),
+            [b'O', b'O', b'O', b' ', _] => Some(Self::CastlingCheckmate {
+                was_played,
+                side: CastlingSide::QueenSide,
+            }),
+            [b'O', b'O', b' ', b' ', _] => Some(Self::CastlingCheckmate {
+                was_played,
+                side: CastlingSide::KingSide,
+            }),
+            [b'=', role, capture, b' ', _] => {
+                Self::parse_underpromotion(*role, *capture, was_played)
+            }
+            [b'E', b'P', b' ', discovered, _] => Some(Self::EnPassantCheckmate {
+                was_played,
+                discovered: *discovered == b'D',
+            }),
+            [b'S', b'M', _, promotion, b' '] => Some(Self::Stalemate {
+                is_capture,
+                promotion: Role::from_char(char::from(promotion.to_ascii_lowercase())),
+            }),
+            [b'M', depth @ .., b'?'] => std::str::from_utf8(depth)?
+                .trim_end()
+                .parse()
+                .ok()
+                .map(|depth| Self::ForcedMate { depth }),
+            _ => None,
+        };
+        move_type.ok_or_else(|| Cause::UnexpectedValue(s.to_string()).into())
+    }
+}
+
+impl MoveType {
+    fn parse_double_disambiguation(code: &[u8], was_played: bool) -> Option<Self> {
+        let checkmate_type = match code.get(3)? {
+            b' ' => CheckType::Normal,
+            b'D' => CheckType::Discovered,
+            b'2' => CheckType::Double,
+            _ => return None,
+        };
+        Some(Self::DoubleDisambiguationCheckmate {
+            was_played,
+            is_capture: code.get(2) == Some(&b'x'),
+            checkmate_type,
+        })
+    }
+
+    fn parse_underpromotion(role: u8, capture: u8, was_played: bool) -> Option<Self> {
+        Some(Self::UnderpromotionCheckmate {
+            was_played,
+            is_capture: capture == b'x',
+            role: Role::from_char(char::from(role.to_ascii_lowercase()))?,
+        })
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
 #[repr(u8)]
 pub enum GameResult {
@@ -108,3 +289,93 @@ pub enum TimeControl {
     BulletTournament = 15,
     UltraBulletTournament = 16,
 }
+
+// "-" (correspondence, no clock) is stored as 0+0, which lichess never uses otherwise
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(C)]
+pub struct Clock {
+    pub base_seconds: u16,
+    pub increment_seconds: u16,
+}
+
+impl Clock {
+    pub const fn is_unlimited(self) -> bool {
+        self.base_seconds == 0 && self.increment_seconds == 0
+    }
+}
+
+impl FromStr for Clock {
+    type Err = Source;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "-" {
+            return Ok(Self::default());
+        }
+        let (base, increment) = s
+            .split_once('+')
+            .ok_or_else(|| Cause::UnexpectedValue(s.to_string()))?;
+        Ok(Self {
+            base_seconds: base.parse()?,
+            increment_seconds: increment.parse()?,
+        })
+    }
+}
+
+impl Display for Clock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_unlimited() {
+            write!(f, "-")
+        } else {
+            write!(f, "{}+{}", self.base_seconds, self.increment_seconds)
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::panic_in_result_fn)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    type Error = crate::error::Source;
+    type Result<T> = std::result::Result<T, Error>;
+
+    #[rstest]
+    #[case(MoveType::KingCheckmate { was_played: false, is_capture: true })]
+    #[case(MoveType::DoubleDisambiguationCheckmate {
+        was_played: true,
+        is_capture: true,
+        checkmate_type: CheckType::Discovered,
+    })]
+    #[case(MoveType::DoubleDisambiguationCheckmate {
+        was_played: false,
+        is_capture: false,
+        checkmate_type: CheckType::Double,
+    })]
+    #[case(MoveType::CastlingCheckmate { was_played: true, side: CastlingSide::KingSide })]
+    #[case(MoveType::CastlingCheckmate { was_played: false, side: CastlingSide::QueenSide })]
+    #[case(MoveType::UnderpromotionCheckmate { was_played: true, is_capture: true, role: Role::Knight })]
+    #[case(MoveType::EnPassantCheckmate { was_played: false, discovered: true })]
+    #[case(MoveType::Stalemate { is_capture: false, promotion: Some(Role::Rook) })]
+    #[case(MoveType::ForcedMate { depth: 3 })]
+    fn test_move_type_roundtrip(#[case] move_type: MoveType) -> Result<()> {
+        let code = move_type.to_string();
+
+        assert_eq!(code.parse::<MoveType>()?, move_type);
+        assert_eq!(code.trim_end().parse::<MoveType>()?, move_type);
+        assert_eq!(
+            serde_json::from_str::<MoveType>(&serde_json::to_string(&move_type)?)?,
+            move_type
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_move_type_invalid() {
+        assert!("DDxX?".parse::<MoveType>().is_err());
+        assert!("=Xx ?".parse::<MoveType>().is_err());
+        assert!("Mx  ?".parse::<MoveType>().is_err());
+        assert!("".parse::<MoveType>().is_err());
+    }
+}
@@ -0,0 +1,150 @@
+use std::fmt::Display;
+
+use serde::{Serialize, Serializer};
+
+use crate::error::{Error, ErrorKind};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureReason {
+    BadEvent,
+    BadResult,
+    UnknownTermination,
+    BadTimeControl,
+    BadDate,
+    BadTime,
+    BadFen,
+    BadGameLink,
+    IllegalMove,
+    AmbiguousMove,
+}
+
+impl FailureReason {
+    pub const ALL: [Self; 10] = [
+        Self::BadEvent,
+        Self::BadResult,
+        Self::UnknownTermination,
+        Self::BadTimeControl,
+        Self::BadDate,
+        Self::BadTime,
+        Self::BadFen,
+        Self::BadGameLink,
+        Self::IllegalMove,
+        Self::AmbiguousMove,
+    ];
+}
+
+impl Display for FailureReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::BadEvent => "bad event",
+            Self::BadResult => "bad result",
+            Self::UnknownTermination => "unknown termination",
+            Self::BadTimeControl => "bad time control",
+            Self::BadDate => "bad date",
+            Self::BadTime => "bad time",
+            Self::BadFen => "bad FEN",
+            Self::BadGameLink => "bad game link",
+            Self::IllegalMove => "illegal move",
+            Self::AmbiguousMove => "ambiguous move",
+        };
+        write!(f, "{s}")
+    }
+}
+
+#[derive(Debug)]
+pub struct GameFailure {
+    pub reason: FailureReason,
+    pub error: Error,
+}
+
+#[derive(Serialize)]
+struct FailureRecord<'a> {
+    game_link: String,
+    ply: Option<u16>,
+    san: Option<&'a str>,
+    reason: FailureReason,
+    message: String,
+}
+
+impl GameFailure {
+    pub const fn new(reason: FailureReason, error: Error) -> Self {
+        Self { reason, error }
+    }
+
+    pub fn in_file(self, file: &str) -> Self {
+        Self {
+            error: self.error.in_file(file),
+            ..self
+        }
+    }
+
+    fn to_record(&self) -> FailureRecord<'_> {
+        let context = self.error.context();
+        FailureRecord {
+            game_link: context
+                .game
+                .as_ref()
+                .map(|id| format!("https://lichess.org/{id}"))
+                .unwrap_or_default(),
+            ply: context.ply,
+            san: match self.error.kind() {
+                ErrorKind::Move(san) => Some(san.as_str()),
+                _ => None,
+            },
+            reason: self.reason,
+            message: self.error.report(),
+        }
+    }
+}
+
+impl Serialize for GameFailure {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_record().serialize(serializer)
+    }
+}
+
+impl Display for GameFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+impl std::error::Error for GameFailure {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::panic_in_result_fn)]
+mod tests {
+    use shakmaty::san::SanError;
+
+    use super::*;
+    use crate::error::Cause;
+
+    #[test]
+    fn test_display_game_failure() {
+        let error = Error::invalid_move("Qxh7".to_string(), SanError::IllegalSan)
+            .at_ply(12)
+            .in_game("abcd1234");
+        let failure = GameFailure::new(FailureReason::IllegalMove, error).in_file("2019-07");
+        assert_eq!(
+            failure.to_string(),
+            "2019-07: move Qxh7 invalid in game abcd1234 at ply 12"
+        );
+    }
+
+    #[test]
+    fn test_serialize_game_failure() -> Result<(), serde_json::Error> {
+        let error =
+            Error::header("Termination", Cause::unexpected(b"Draw offer")).in_game("abcd1234");
+        let failure = GameFailure::new(FailureReason::UnknownTermination, error);
+        assert_eq!(
+            serde_json::to_string(&failure)?,
+            r#"{"game_link":"https://lichess.org/abcd1234","ply":null,"san":null,"reason":"unknown_termination","message":"header Termination malformed in game abcd1234: unexpected value \"Draw offer\""}"#
+        );
+        Ok(())
+    }
+}
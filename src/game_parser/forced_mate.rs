@@ -0,0 +1,128 @@
+use shakmaty::{Chess, Move, MoveList, Position};
+
+// bounded mate search that only follows checks and captures for the attacking side,
+// quiet first moves of a forced mate are not found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForcedMateSearch {
+    pub max_depth: u8,
+    pub max_forcing_moves: usize,
+    pub log_min_elo: i16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForcedMate {
+    pub depth: u8,
+    pub first_move: Move,
+}
+
+impl ForcedMateSearch {
+    // mates in one are left to the scan over all legal moves
+    pub fn find(&self, position: &Chess) -> Option<ForcedMate> {
+        let forcing = forcing_moves(position);
+        if forcing.len() > self.max_forcing_moves || self.has_mate(position, &forcing, 1) {
+            return None;
+        }
+        (2..=self.max_depth).find_map(|depth| {
+            forcing
+                .iter()
+                .find(|m| self.forces_mate(position, m, depth))
+                .map(|m| ForcedMate {
+                    depth,
+                    first_move: m.clone(),
+                })
+        })
+    }
+
+    // after m every defence runs into mate within depth moves, m itself counting as the first
+    pub fn forces_mate(&self, position: &Chess, m: &Move, depth: u8) -> bool {
+        let mut position = position.clone();
+        position.play_unchecked(m);
+        let replies = position.legal_moves();
+        if replies.is_empty() {
+            return position.is_checkmate();
+        }
+        depth > 1
+            && replies.iter().all(|reply| {
+                let mut position = position.clone();
+                position.play_unchecked(reply);
+                self.has_mate(&position, &forcing_moves(&position), depth - 1)
+            })
+    }
+
+    fn has_mate(&self, position: &Chess, forcing: &MoveList, depth: u8) -> bool {
+        forcing.iter().any(|m| self.forces_mate(position, m, depth))
+    }
+}
+
+fn forcing_moves(position: &Chess) -> MoveList {
+    let mut moves = position.legal_moves();
+    moves.retain(|m| m.is_capture() || gives_check(position, m));
+    moves
+}
+
+fn gives_check(position: &Chess, m: &Move) -> bool {
+    let mut position = position.clone();
+    position.play_unchecked(m);
+    position.is_check()
+}
+
+#[cfg(test)]
+#[allow(clippy::panic_in_result_fn)]
+mod tests {
+    use std::str::FromStr;
+
+    use shakmaty::{fen::Fen, san::San, CastlingMode};
+
+    use super::*;
+
+    type Error = Box<dyn std::error::Error>;
+    type Result<T> = std::result::Result<T, Error>;
+
+    const SMOTHERED_IN_TWO: &str = "3r3k/6pp/7N/8/2Q5/8/8/6K1 w - - 0 1";
+
+    fn search() -> ForcedMateSearch {
+        ForcedMateSearch {
+            max_depth: 3,
+            max_forcing_moves: 8,
+            log_min_elo: 0,
+        }
+    }
+
+    #[test]
+    fn test_find_forced_mate() -> Result<()> {
+        let fen: Fen = SMOTHERED_IN_TWO.parse()?;
+        let position: Chess = fen.into_position(CastlingMode::Standard)?;
+        let first_move = San::from_str("Qg8")?.to_move(&position)?;
+
+        assert_eq!(
+            search().find(&position),
+            Some(ForcedMate {
+                depth: 2,
+                first_move
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_skips_mate_in_one() -> Result<()> {
+        let fen: Fen = "6k1/5ppp/8/8/8/8/8/3R2K1 w - - 0 1".parse()?;
+        let position: Chess = fen.into_position(CastlingMode::Standard)?;
+
+        assert_eq!(search().find(&position), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_forces_mate() -> Result<()> {
+        let fen: Fen = SMOTHERED_IN_TWO.parse()?;
+        let position: Chess = fen.into_position(CastlingMode::Standard)?;
+        let queen_sacrifice = San::from_str("Qg8")?.to_move(&position)?;
+        let knight_check = San::from_str("Nf7")?.to_move(&position)?;
+
+        assert!(search().forces_mate(&position, &queen_sacrifice, 2));
+        assert!(!search().forces_mate(&position, &queen_sacrifice, 1));
+        assert!(!search().forces_mate(&position, &knight_check, 2));
+        Ok(())
+    }
+}
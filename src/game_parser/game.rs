@@ -1,26 +1,88 @@
-use crate::{Error, Result};
+use crate::error::{Error, Source};
+use shakmaty::fen::Fen;
 use shakmaty::san::{San, SanError};
-use shakmaty::{Chess, Position};
+use shakmaty::{CastlingMode, Chess, Position};
 
+use super::annotated_pgn::AnnotatedPgn;
+use super::draw::DrawTracker;
 use super::enums::GameResult;
-use super::GameData;
+use super::failure::{FailureReason, GameFailure};
+use super::material::MaterialTracker;
+use super::{Analysis, GameData};
 
 #[derive(Debug, Default, Clone)]
 pub struct Game {
     pub sans: Vec<San>,
-    pub success: bool,
     pub data: GameData,
+    pub start_position: Chess,
+    // only filled when the validator keeps them
+    pub headers: Vec<(String, String)>,
+}
+
+// what a validated game leaves behind
+#[derive(Debug)]
+pub struct Replayed {
+    pub data: GameData,
+    // the annotated pgn of a game with rare moves, when rare_pgn is on
+    pub rare_pgn: Option<String>,
+    // every position with the ply it was first reached after, when the census is on
+    pub positions: Vec<(u64, u16)>,
 }
 
 impl Game {
-    pub fn validate(mut self) -> Result<GameData> {
-        let mut position = Chess::default();
-        self.sans
-            .iter()
-            .enumerate()
-            .try_for_each(|(ply, san)| Self::check_move(&mut position, &mut self.data, ply, san))?;
+    #[cfg(test)]
+    pub fn validate(self, analysis: &Analysis) -> Result<GameData, GameFailure> {
+        self.validate_with_pgn(analysis)
+            .map(|replayed| replayed.data)
+    }
+
+    // with rare_pgn, a game that produced rare moves comes back with its annotated pgn
+    pub fn validate_with_pgn(mut self, analysis: &Analysis) -> Result<Replayed, GameFailure> {
+        let positions = self.replay(analysis)?;
+        let rare_pgn = (analysis.rare_pgn && self.data.has_rare_moves())
+            .then(|| AnnotatedPgn(&self).to_string());
+        Ok(Replayed {
+            data: self.data,
+            rare_pgn,
+            positions,
+        })
+    }
+
+    fn replay(&mut self, analysis: &Analysis) -> Result<Vec<(u64, u16)>, GameFailure> {
+        let mut position = self.start_position.clone();
+        // keep white on even plies when a custom position starts with black to move
+        let first_ply = usize::from(position.turn().is_black());
+        let mut draws = DrawTracker::new(&position);
+        let mut material = MaterialTracker::new(&position);
+        self.data.check_piece_counts(position.board(), 0);
+        self.sans.iter().enumerate().try_for_each(|(ply, san)| {
+            Self::check_move(
+                &mut position,
+                &mut self.data,
+                first_ply + ply,
+                san,
+                analysis,
+            )?;
+            draws.update(&position, first_ply + ply);
+            material.update(&position);
+            Ok(())
+        })?;
+        draws.finish(&mut self.data, &position);
+        material.finish(&mut self.data, &position);
         self.data.half_moves = self.sans.len() as u16;
-        Ok(self.data)
+        Ok(if analysis.census {
+            draws.positions().collect()
+        } else {
+            Vec::new()
+        })
+    }
+
+    pub fn parse_fen(&mut self, value: &[u8]) -> Result<(), Source> {
+        let fen = Fen::from_ascii(value)?;
+        let castling_mode = CastlingMode::detect(fen.as_setup());
+        self.start_position = fen.into_position(castling_mode)?;
+        self.data.from_position = true;
+        Ok(())
     }
 
     fn check_move(
@@ -28,14 +90,16 @@ impl Game {
         game_data: &mut GameData,
         ply: usize,
         san: &San,
-    ) -> Result<()> {
+        analysis: &Analysis,
+    ) -> Result<(), GameFailure> {
         let m = san
             .to_move(position)
-            .map_err(|err| to_error(game_data, san, err))?;
+            .map_err(|err| to_failure(game_data, ply, san, err))?;
         let is_winner = Self::check_is_winner(game_data.result, ply);
 
-        game_data.analyze_position(position, ply, &m, is_winner);
+        game_data.analyze_position(position, ply, &m, is_winner, analysis);
         position.play_unchecked(&m);
+        game_data.check_piece_counts(position.board(), ply);
         Ok(())
     }
 
@@ -47,15 +111,15 @@ impl Game {
     }
 }
 
-fn to_error(game_data: &GameData, san: &San, err: SanError) -> Error {
-    let game_link = game_data.get_formatted_game_link();
-    match game_link {
-        Ok(game_link) => {
-            let message = format!("Invalid move: {san:?} in game {game_link}");
-            Error::InvalidMove(err, message)
-        }
-        Err(e) => e,
-    }
+fn to_failure(game_data: &GameData, ply: usize, san: &San, err: SanError) -> GameFailure {
+    let reason = match err {
+        SanError::IllegalSan => FailureReason::IllegalMove,
+        SanError::AmbiguousSan => FailureReason::AmbiguousMove,
+    };
+    let error = Error::invalid_move(san.to_string(), err)
+        .at_ply(ply)
+        .in_game(&game_data.get_game_id());
+    GameFailure::new(reason, error)
 }
 
 #[cfg(test)]
@@ -65,10 +129,11 @@ mod tests {
 
     use super::*;
 
-    type Error = Box<dyn std::error::Error>;
+    type BoxError = Box<dyn std::error::Error>;
 
     #[bench]
     fn bench_check_move(b: &mut test::Bencher) {
+        let analysis = Analysis::default();
         b.iter(|| {
             let mut game_data = GameData::default();
             let position = Chess::default();
@@ -77,10 +142,10 @@ mod tests {
                 for m in position.legal_moves() {
                     let san = San::from_move(&position, &m);
                     let mut board_copy = position.clone();
-                    Game::check_move(&mut board_copy, &mut game_data, 0, &san)?;
+                    Game::check_move(&mut board_copy, &mut game_data, 0, &san, &analysis)?;
                 }
             }
-            Ok::<(), Error>(())
+            Ok::<(), BoxError>(())
         });
     }
 }
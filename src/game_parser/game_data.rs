@@ -1,16 +1,23 @@
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr, str::Utf8Error};
 
-use chrono::naive::{NaiveDate, NaiveTime};
-use shakmaty::{Chess, Move, Position};
+use chrono::{
+    naive::{NaiveDate, NaiveTime},
+    DateTime,
+};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use shakmaty::{Board, Chess, Color, Move, Position, Role};
 
-use crate::Result;
+use crate::error::{Cause, Source};
 
 use super::{
-    enums::{GameResult, Termination, TimeControl},
-    game_player_data::RareMove,
-    GamePlayerData, MoveType,
+    enums::{Clock, GameResult, Termination, TimeControl},
+    game_player_data::{PlayedMove, RareMove},
+    material, Analysis, DrawType, GamePlayerData, MatePatterns, MoveType, RareMoveDetectors,
 };
 
+const COMEBACK_DEFICIT: u8 = 3;
+
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 #[repr(C)]
 pub struct GameData {
@@ -19,18 +26,44 @@ pub struct GameData {
     pub start_time: u32,
     pub move_variations: u32,
     pub game_link: [u8; 8],
+    pub clock: Clock,
     pub time_control: TimeControl,
     pub result: GameResult,
     pub termination: Termination,
     pub half_moves: u16,
+    pub mate_patterns: MatePatterns,
+    pub draw_type: DrawType,
+    // first draw claim left untaken in a decisive game
+    pub unclaimed_draw: DrawType,
+    pub unclaimed_draw_ply: u16,
+    pub max_queens: u8,
+    // ply of the move that first put max_queens on the board
+    pub max_queens_ply: u16,
+    pub from_position: bool,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+// the field names are the column names of the json lines output
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct RareMoveWithLink {
     pub game_link: String,
     pub san: String,
     pub ply: u16,
     pub move_type: MoveType,
+    // enough of the game to check the move without opening lichess
+    pub played: String,
+    pub played_mates: bool,
+    pub won: bool,
+    pub available_plies: u16,
+    pub white: String,
+    pub white_elo: i16,
+    pub black: String,
+    pub black_elo: i16,
+    #[serde_as(as = "DisplayFromStr")]
+    pub date: NaiveDate,
+    #[serde_as(as = "DisplayFromStr")]
+    pub clock: Clock,
+    pub fen: String,
 }
 
 impl Display for RareMoveWithLink {
@@ -40,30 +73,178 @@ impl Display for RareMoveWithLink {
             self.ply / 2 + 1,
             if self.ply % 2 == 0 { ".  " } else { "..." }
         );
-        let san_plus = format!("{}#", self.san);
+        let san_plus = format!(
+            "{}{}",
+            self.san,
+            if self.move_type.is_checkmate() {
+                "#"
+            } else {
+                ""
+            }
+        );
         write!(
             f,
             "{move_number_str} {san_plus:7},{},{}",
             self.move_type, self.game_link
+        )?;
+        // rows of the legacy catalogue stay in its three columns
+        if self.fen.is_empty() {
+            return Ok(());
+        }
+        // columns added later go at the end, so rows of earlier runs still parse
+        write!(
+            f,
+            ",{},{},{},{},{},{},{},{},{},{},{}",
+            self.played,
+            self.white,
+            self.white_elo,
+            self.black,
+            self.black_elo,
+            self.date,
+            self.clock,
+            self.fen,
+            self.available_plies,
+            self.played_mates,
+            self.won
         )
     }
 }
 
+// reads back what Display wrote, rows of the legacy catalogue only have the first three columns
+// and rows of earlier runs stop before the columns added since
+impl FromStr for RareMoveWithLink {
+    type Err = Source;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields = s.split(',').collect::<Vec<_>>();
+        let [move_str, move_type, game_link, context @ ..] = fields.as_slice() else {
+            return Err(Cause::UnexpectedValue(s.to_string()).into());
+        };
+        let (ply, san) = parse_move_number(move_str)?;
+        let rare_move = Self {
+            game_link: game_link.trim().to_string(),
+            san,
+            ply,
+            move_type: move_type.parse()?,
+            played: String::new(),
+            played_mates: false,
+            won: false,
+            available_plies: 1,
+            white: String::new(),
+            white_elo: 0,
+            black: String::new(),
+            black_elo: 0,
+            date: NaiveDate::default(),
+            clock: Clock::default(),
+            fen: String::new(),
+        };
+        match context {
+            [] => Ok(rare_move),
+            [played, white, white_elo, black, black_elo, date, clock, fen, appended @ ..] => Self {
+                played: String::from(*played),
+                white: String::from(*white),
+                white_elo: white_elo.parse()?,
+                black: String::from(*black),
+                black_elo: black_elo.parse()?,
+                date: date.parse()?,
+                clock: clock.parse()?,
+                fen: String::from(*fen),
+                ..rare_move
+            }
+            .with_appended(appended),
+            _ => Err(Cause::UnexpectedValue(s.to_string()).into()),
+        }
+    }
+}
+
+// " 62... Nf8e6# " -> (123, "Nf8e6")
+fn parse_move_number(s: &str) -> Result<(u16, String), Source> {
+    let Some((number, san)) = s.trim().split_once(' ') else {
+        return Err(Cause::UnexpectedValue(s.to_string()).into());
+    };
+    let (move_number, black) = number.strip_suffix("...").map_or_else(
+        || (number.trim_end_matches('.'), 0),
+        |move_number| (move_number, 1),
+    );
+    let ply = move_number.parse::<u16>()?.saturating_sub(1) * 2 + black;
+    Ok((ply, san.trim().trim_end_matches('#').to_string()))
+}
+
 impl RareMoveWithLink {
-    pub fn new(game_link: String, rare_move: &RareMove) -> Self {
+    // available_plies came with merging repeated moves, played_mates and won after it
+    fn with_appended(self, columns: &[&str]) -> Result<Self, Source> {
+        match columns {
+            [] => Ok(self),
+            [available_plies] => Ok(Self {
+                available_plies: available_plies.parse()?,
+                ..self
+            }),
+            [available_plies, played_mates, won] => Ok(Self {
+                available_plies: available_plies.parse()?,
+                played_mates: played_mates.parse()?,
+                won: won.parse()?,
+                ..self
+            }),
+            _ => Err(Cause::UnexpectedValue(columns.join(",")).into()),
+        }
+    }
+
+    // the fen without the move counters, the same in every game reaching the position
+    pub fn position(&self) -> &str {
+        self.fen
+            .match_indices(' ')
+            .nth(3)
+            .map_or(self.fen.as_str(), |(end, _)| &self.fen[..end])
+    }
+
+    // the same rare move in the same position, whichever game reached it
+    pub fn position_key(&self) -> String {
+        format!("{} {} {}", self.position(), self.san, self.move_type)
+    }
+
+    pub fn new(game_data: &GameData, rare_move: &RareMove) -> Self {
         Self {
-            game_link,
+            game_link: game_data.get_formatted_game_link().unwrap_or_default(),
             san: rare_move.san.clone(),
             ply: rare_move.ply,
             move_type: rare_move.move_type.clone(),
+            played: rare_move.played.clone(),
+            played_mates: rare_move.played_mates,
+            won: rare_move.won,
+            available_plies: rare_move.available_plies,
+            white: game_data.white_player.get_name(),
+            white_elo: game_data.white_player.elo,
+            black: game_data.black_player.get_name(),
+            black_elo: game_data.black_player.elo,
+            date: game_data.get_start_date().unwrap_or_default(),
+            clock: game_data.clock,
+            fen: rare_move.fen.clone(),
         }
     }
 }
 
 impl GameData {
-    pub fn analyze_position(&mut self, pos: &Chess, ply: usize, m: &Move, is_winner: bool) {
-        self.check_move(pos, ply, m);
-        self.check_possible_moves(pos, ply, m, is_winner);
+    pub fn analyze_position(
+        &mut self,
+        pos: &Chess,
+        ply: usize,
+        m: &Move,
+        is_winner: bool,
+        analysis: &Analysis,
+    ) {
+        let played = PlayedMove::new(pos, m, is_winner);
+        self.check_move(pos, ply, m, &analysis.detectors);
+        self.check_other_moves(pos, ply, m, played, &analysis.detectors);
+        if let Some(search) = &analysis.forced_mates {
+            self.get_player_data(ply)
+                .check_forced_mate(search, pos, m, ply);
+        }
+        self.get_player_data(ply).set_played(pos, m, ply, played);
+    }
+
+    pub const fn has_rare_moves(&self) -> bool {
+        !self.white_player.rare_checkmates.is_empty()
+            || !self.black_player.rare_checkmates.is_empty()
     }
 
     pub fn get_rare_moves(&self) -> Vec<RareMoveWithLink> {
@@ -71,16 +252,17 @@ impl GameData {
             .rare_checkmates
             .iter()
             .chain(self.black_player.rare_checkmates.iter())
-            .map(move |rare_move| {
-                RareMoveWithLink::new(
-                    self.get_formatted_game_link().unwrap_or_default(),
-                    rare_move,
-                )
-            })
+            .map(|rare_move| RareMoveWithLink::new(self, rare_move))
             .collect()
     }
 
-    fn check_move(&mut self, position: &Chess, ply: usize, m: &Move) {
+    fn check_move(
+        &mut self,
+        position: &Chess,
+        ply: usize,
+        m: &Move,
+        detectors: &RareMoveDetectors,
+    ) {
         let is_en_passant = m.is_en_passant();
         let mut board_copy = position.clone();
         board_copy.play_unchecked(m);
@@ -89,46 +271,50 @@ impl GameData {
         let player_data = self.get_player_data(ply);
         player_data.en_passant_mates += u8::from(is_en_passant_mate);
         player_data.en_passants += u8::from(is_en_passant);
+        player_data.promotions += u8::from(m.promotion().is_some());
+        player_data.check_underpromotion(position, m, is_checkmate);
         if is_checkmate {
-            if let Some(rare_move) = GamePlayerData::check_rare_move(position.clone(), m, ply, true)
-            {
-                player_data.rare_checkmates.push(rare_move);
-            }
+            player_data.check_rare_move(detectors, position, m, ply, true);
+            self.mate_patterns = MatePatterns::classify(&board_copy);
+        } else if board_copy.is_stalemate() {
+            player_data.check_stalemate(position, m, ply);
         }
     }
 
-    fn check_possible_moves(&mut self, pos: &Chess, ply: usize, m: &Move, is_winner: bool) {
-        let mut board_copy = pos.clone();
-        board_copy.play_unchecked(m);
-        let is_checkmate = board_copy.is_checkmate();
-        self.check_other_moves(pos, ply, m, is_winner, is_checkmate);
-    }
-
     fn check_other_moves(
         &mut self,
         pos: &Chess,
         ply: usize,
         m: &Move,
-        is_winner: bool,
-        is_checkmate: bool,
+        played: PlayedMove,
+        detectors: &RareMoveDetectors,
     ) {
         let possible_moves = pos.legal_moves();
         self.move_variations += possible_moves.len() as u32;
         let player_data = self.get_player_data(ply);
 
-        possible_moves
+        let mut stalemates = false;
+        for possible_move in possible_moves
             .iter()
-            .filter(|&possible_move| !possible_move.eq(m))
-            .for_each(|possible_move| {
-                player_data.check_other_move(
-                    pos.clone(),
-                    possible_move,
-                    ply,
-                    is_winner,
-                    is_checkmate,
-                );
-                player_data.check_declined_en_passant(m, possible_move);
-            });
+            .filter(|&possible_move| possible_move != m)
+        {
+            stalemates |=
+                player_data.check_other_move(pos.clone(), possible_move, ply, played, detectors);
+            player_data.check_declined_en_passant(m, possible_move);
+        }
+        if stalemates {
+            player_data.check_missed_stalemate(pos);
+        }
+    }
+
+    pub fn check_piece_counts(&mut self, board: &Board, ply: usize) {
+        self.white_player.check_piece_counts(board, Color::White);
+        self.black_player.check_piece_counts(board, Color::Black);
+        let queens = board.queens().count() as u8;
+        if queens > self.max_queens {
+            self.max_queens = queens;
+            self.max_queens_ply = ply as u16;
+        }
     }
 
     #[allow(unused)]
@@ -136,13 +322,44 @@ impl GameData {
         self.white_player.en_passant_mates > 0 || self.black_player.en_passant_mates > 0
     }
 
-    pub fn get_formatted_game_link(&self) -> Result<String> {
+    pub fn get_formatted_game_link(&self) -> Result<String, Utf8Error> {
         Ok(format!(
             "https://lichess.org/{}",
             std::str::from_utf8(&self.game_link)?
         ))
     }
 
+    pub fn get_start_date(&self) -> Option<NaiveDate> {
+        DateTime::from_timestamp(i64::from(self.start_time), 0)
+            .map(|datetime| datetime.date_naive())
+    }
+
+    pub fn get_game_id(&self) -> String {
+        String::from_utf8_lossy(&self.game_link).to_string()
+    }
+
+    pub const fn get_winner(&self) -> Option<&GamePlayerData> {
+        match self.result {
+            GameResult::WhiteWin => Some(&self.white_player),
+            GameResult::BlackWin => Some(&self.black_player),
+            _ => None,
+        }
+    }
+
+    // the winner was down at least a minor piece at some point
+    pub const fn is_comeback(&self) -> bool {
+        match self.get_winner() {
+            Some(winner) => winner.max_deficit >= COMEBACK_DEFICIT,
+            None => false,
+        }
+    }
+
+    // the winner was at least a queen behind at some point of the game
+    pub fn won_down_a_queen(&self) -> bool {
+        self.get_winner()
+            .is_some_and(|winner| i32::from(winner.max_deficit) >= material::value(Role::Queen))
+    }
+
     pub fn get_player_data(&mut self, half_move_number: usize) -> &mut GamePlayerData {
         match half_move_number % 2 {
             0 => &mut self.white_player,
@@ -150,19 +367,19 @@ impl GameData {
         }
     }
 
-    pub fn parse_result(&mut self, value: &[u8]) -> Result<()> {
+    pub fn parse_result(&mut self, value: &[u8]) -> Result<(), Source> {
         let s = std::str::from_utf8(value)?;
         self.result = match s {
             "1-0" => GameResult::WhiteWin,
             "1/2-1/2" => GameResult::Draw,
             "0-1" => GameResult::BlackWin,
             "*" => GameResult::Unfinished, // some correspondence games take more than a month to complete
-            _ => Err(value.to_vec())?,
+            _ => Err(Cause::unexpected(value))?,
         };
         Ok(())
     }
 
-    pub fn parse_termination(&mut self, value: &[u8]) -> Result<()> {
+    pub fn parse_termination(&mut self, value: &[u8]) -> Result<(), Source> {
         let s = std::str::from_utf8(value)?;
         self.termination = match s {
             "Normal" => Termination::Normal,
@@ -170,12 +387,12 @@ impl GameData {
             "Abandoned" => Termination::Abandoned,
             "Unterminated" => Termination::Unterminated,
             "Rules infraction" => Termination::RulesInfraction,
-            _ => Err(value.to_vec())?,
+            _ => Err(Cause::unexpected(value))?,
         };
         Ok(())
     }
 
-    pub fn parse_time_control(&mut self, value: &[u8]) -> Result<()> {
+    pub fn parse_time_control(&mut self, value: &[u8]) -> Result<(), Source> {
         let s = std::str::from_utf8(value)?;
         let l = s.split(' ').collect::<Vec<&str>>();
 
@@ -183,12 +400,12 @@ impl GameData {
             ["Rated", speed, "game"] => Self::parse_rated_time_control(speed)?,
             ["Rated", speed, "tournament", _] => Self::parse_tournament_time_control(speed)?,
             [speed, "swiss", _] => Self::parse_tournament_time_control(speed)?,
-            _ => Err(value.to_vec())?,
+            _ => Err(Cause::unexpected(value))?,
         };
         Ok(())
     }
 
-    fn parse_rated_time_control(speed: &str) -> Result<TimeControl> {
+    fn parse_rated_time_control(speed: &str) -> Result<TimeControl, Source> {
         Ok(match speed {
             "Correspondence" => TimeControl::CorrespondenceGame,
             "Classical" => TimeControl::ClassicalGame,
@@ -197,11 +414,11 @@ impl GameData {
             "Blitz" => TimeControl::BlitzGame,
             "Bullet" => TimeControl::BulletGame,
             "UltraBullet" => TimeControl::UltraBulletGame,
-            _ => Err(speed.to_string())?,
+            _ => Err(Cause::UnexpectedValue(speed.to_string()))?,
         })
     }
 
-    fn parse_tournament_time_control(speed: &str) -> Result<TimeControl> {
+    fn parse_tournament_time_control(speed: &str) -> Result<TimeControl, Source> {
         Ok(match speed {
             "Correspondence" => TimeControl::CorrespondenceTournament,
             "Classical" => TimeControl::ClassicalTournament,
@@ -210,85 +427,272 @@ impl GameData {
             "Blitz" => TimeControl::BlitzTournament,
             "Bullet" => TimeControl::BulletTournament,
             "UltraBullet" => TimeControl::UltraBulletTournament,
-            _ => Err(speed.to_string())?,
+            _ => Err(Cause::UnexpectedValue(speed.to_string()))?,
         })
     }
 
+    pub fn parse_clock(&mut self, value: &[u8]) -> Result<(), Source> {
+        self.clock = std::str::from_utf8(value)?.parse()?;
+        Ok(())
+    }
+
     pub fn parse_site(&mut self, value: &[u8]) {
         let l = value.len();
         self.game_link[..8].clone_from_slice(&value[l - 8..l]);
     }
 
-    pub fn parse_date(&mut self, value: &[u8]) -> Result<()> {
+    pub fn parse_date(&mut self, value: &[u8]) -> Result<(), Source> {
         let s = std::str::from_utf8(value)?;
         let date = NaiveDate::parse_from_str(s, "%Y.%m.%d")?;
-        let datetime = date.and_hms_opt(0, 0, 0).ok_or_else(|| value.to_vec())?;
+        let datetime = date
+            .and_hms_opt(0, 0, 0)
+            .ok_or_else(|| Cause::unexpected(value))?;
         self.start_time += datetime.and_utc().timestamp() as u32;
         Ok(())
     }
 
-    pub fn parse_time(&mut self, value: &[u8]) -> Result<()> {
+    pub fn parse_time(&mut self, value: &[u8]) -> Result<(), Source> {
         let s = std::str::from_utf8(value)?;
         let time = NaiveTime::parse_from_str(s, "%H:%M:%S")?;
-        let midnight = NaiveTime::from_hms_opt(0, 0, 0).ok_or_else(|| value.to_vec())?;
+        let midnight = NaiveTime::from_hms_opt(0, 0, 0).ok_or_else(|| Cause::unexpected(value))?;
         self.start_time += time.signed_duration_since(midnight).num_seconds() as u32;
         Ok(())
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::panic_in_result_fn)]
 mod tests {
     extern crate test;
+    use rstest::rstest;
+
     use super::*;
     use crate::game_parser::enums::CheckType;
 
+    #[rstest]
+    #[case(b"600+0", 600, 0)]
+    #[case(b"180+2", 180, 2)]
+    #[case(b"0+1", 0, 1)]
+    #[case(b"-", 0, 0)]
+    fn test_parse_clock(
+        #[case] value: &[u8],
+        #[case] base_seconds: u16,
+        #[case] increment_seconds: u16,
+    ) -> Result<(), Source> {
+        let mut game_data = GameData::default();
+        game_data.parse_clock(value)?;
+        let expected = Clock {
+            base_seconds,
+            increment_seconds,
+        };
+        assert_eq!(game_data.clock, expected);
+        assert_eq!(
+            game_data.clock.to_string(),
+            std::str::from_utf8(value)?.to_string()
+        );
+        Ok(())
+    }
+
     #[test]
-    fn test_display_rare_move() {
-        let game_link = "https://lichess.org/123".to_string();
-        let rare_move = RareMove {
+    fn test_parse_clock_invalid() {
+        let mut game_data = GameData::default();
+        assert!(game_data.parse_clock(b"600").is_err());
+        assert!(game_data.parse_clock(b"a+b").is_err());
+    }
+
+    fn rare_move_game() -> GameData {
+        let mut game_data = GameData {
+            game_link: *b"abcd1234",
+            // 2024-06-09
+            start_time: 1_717_891_200,
+            clock: Clock {
+                base_seconds: 180,
+                increment_seconds: 2,
+            },
+            ..Default::default()
+        };
+        game_data.white_player.set_name(b"alice");
+        game_data.white_player.elo = 1850;
+        game_data.black_player.set_name(b"bob");
+        game_data.black_player.elo = 1790;
+        game_data
+    }
+
+    fn double_check_mate(ply: u16) -> RareMove {
+        RareMove {
             san: "Nf8e6".to_string(),
-            ply: 10,
+            ply,
             move_type: MoveType::DoubleDisambiguationCheckmate {
                 was_played: true,
                 is_capture: false,
                 checkmate_type: CheckType::Double,
             },
-        };
-        let rare_move_with_link = RareMoveWithLink::new(game_link, &rare_move);
+            fen: "N2NQN1k/7p/7B/7R/5N2/7P/6B1/6K1 w - - 0 1".to_string(),
+            played: "Nf8e6".to_string(),
+            played_mates: true,
+            won: true,
+            position_hash: 0,
+            available_plies: 3,
+        }
+    }
+
+    #[test]
+    fn test_display_rare_move() {
+        let rare_move_with_link = RareMoveWithLink::new(&rare_move_game(), &double_check_mate(10));
         assert_eq!(
             rare_move_with_link.to_string(),
-            "  6.   Nf8e6# ,DD 2 ,https://lichess.org/123"
+            "  6.   Nf8e6# ,DD 2 ,https://lichess.org/abcd1234,Nf8e6,alice,1850,bob,1790,2024-06-09,\
+             180+2,N2NQN1k/7p/7B/7R/5N2/7P/6B1/6K1 w - - 0 1,3,true,true"
         );
     }
 
+    #[test]
+    fn test_parse_rows_of_earlier_runs() -> Result<(), Source> {
+        let row = "  6.   Nf8e6# ,DD 2 ,https://lichess.org/abcd1234,Nf8e6,alice,1850,bob,1790,\
+                   2024-06-09,180+2,N2NQN1k/7p/7B/7R/5N2/7P/6B1/6K1 w - - 0 1";
+        let rare_move = row.parse::<RareMoveWithLink>()?;
+        let with_plies = format!("{row},3").parse::<RareMoveWithLink>()?;
+
+        assert_eq!(
+            (
+                rare_move.black_elo,
+                rare_move.available_plies,
+                rare_move.won,
+                with_plies.available_plies
+            ),
+            (1790, 1, false, 3)
+        );
+        assert!(format!("{row},3,true").parse::<RareMoveWithLink>().is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_display_rare_move_odd_ply() {
-        let game_link = "https://lichess.org/123".to_string();
-        let rare_move = RareMove {
-            san: "Nf8e6".to_string(),
-            ply: 123,
-            move_type: MoveType::DoubleDisambiguationCheckmate {
+        let rare_move_with_link = RareMoveWithLink::new(&rare_move_game(), &double_check_mate(123));
+        assert!(rare_move_with_link
+            .to_string()
+            .starts_with(" 62... Nf8e6# ,DD 2 ,https://lichess.org/abcd1234,"));
+    }
+
+    #[test]
+    fn test_parse_rare_move() -> Result<(), Source> {
+        let rare_move = RareMoveWithLink::new(&rare_move_game(), &double_check_mate(123));
+
+        assert_eq!(
+            rare_move.to_string().parse::<RareMoveWithLink>()?,
+            rare_move
+        );
+        assert_eq!(
+            rare_move.position(),
+            "N2NQN1k/7p/7B/7R/5N2/7P/6B1/6K1 w - -"
+        );
+        let json = serde_json::to_string(&rare_move)?;
+        assert!(json.contains(r#""move_type":"DD 2 ""#) && json.contains(r#""clock":"180+2""#));
+        assert_eq!(serde_json::from_str::<RareMoveWithLink>(&json)?, rare_move);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_legacy_catalogue() -> Result<(), Source> {
+        let catalogue = include_str!("../../rare_checkmates.csv");
+        let rare_moves = catalogue
+            .lines()
+            .map(str::parse::<RareMoveWithLink>)
+            .collect::<Result<Vec<_>, _>>()?;
+        let first = &rare_moves[0];
+
+        assert_eq!(rare_moves.len(), catalogue.lines().count());
+        assert_eq!(
+            (first.ply, first.san.as_str(), first.game_link.as_str()),
+            (79, "Kxf7", "https://lichess.org/fdyz2wxy")
+        );
+        assert_eq!(
+            first.move_type,
+            MoveType::KingCheckmate {
                 was_played: true,
-                is_capture: false,
-                checkmate_type: CheckType::Double,
-            },
+                is_capture: true
+            }
+        );
+        assert_eq!(
+            first.to_string().split(',').take(3).collect::<Vec<_>>(),
+            [" 40... Kxf7#  ", "K x  ", "https://lichess.org/fdyz2wxy"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_avoided_stalemate_counted_once_per_ply() -> Result<(), Source> {
+        let position: Chess = "k7/8/8/8/8/8/8/1Q5K w - - 0 1"
+            .parse::<shakmaty::fen::Fen>()?
+            .into_position(shakmaty::CastlingMode::Standard)?;
+        let m = shakmaty::san::San::from_ascii(b"Qb2")?.to_move(&position)?;
+        let played = PlayedMove::new(&position, &m, true);
+        let mut game_data = GameData::default();
+        // Qb6 and Qc7 both stalemate
+        game_data.check_other_moves(&position, 0, &m, played, &RareMoveDetectors::default());
+
+        assert_eq!(game_data.white_player.avoided_stalemates, 1);
+        Ok(())
+    }
+
+    // .data files are read back with the dtypes in visualize/common.py
+    #[test]
+    fn test_exported_layout() {
+        use std::mem::{offset_of, size_of};
+
+        assert_eq!(
+            (size_of::<GamePlayerData>(), size_of::<GameData>()),
+            (80, 200)
+        );
+        assert_eq!(
+            (
+                offset_of!(GamePlayerData, rare_checkmates),
+                offset_of!(GameData, clock),
+                offset_of!(GameData, from_position)
+            ),
+            (56, 176, 196)
+        );
+    }
+
+    #[test]
+    fn test_won_down_a_queen() {
+        let mut game_data = GameData {
+            result: GameResult::BlackWin,
+            ..Default::default()
         };
-        let rare_move_with_link = RareMoveWithLink::new(game_link, &rare_move);
+        game_data.white_player.final_material = 14;
+        game_data.black_player.final_material = 5;
+        game_data.black_player.max_deficit = 9;
+
         assert_eq!(
-            rare_move_with_link.to_string(),
-            " 62... Nf8e6# ,DD 2 ,https://lichess.org/123"
+            (game_data.won_down_a_queen(), game_data.is_comeback()),
+            (true, true)
+        );
+
+        // the material recovered by the end of the game
+        game_data.black_player.final_material = 14;
+        assert!(game_data.won_down_a_queen());
+
+        game_data.black_player.max_deficit = 8;
+        assert!(!game_data.won_down_a_queen());
+
+        game_data.result = GameResult::WhiteWin;
+        assert_eq!(
+            (game_data.won_down_a_queen(), game_data.is_comeback()),
+            (false, false)
         );
     }
 
     #[bench]
     fn bench_analyze_position(b: &mut test::Bencher) {
+        let analysis = Analysis::default();
         b.iter(|| {
             let mut game_data = GameData::default();
             let position = Chess::default();
             // game has average of 60 moves, 20 legal moves in starting position
             for _ in 0..3 {
                 for m in position.legal_moves() {
-                    game_data.analyze_position(&position, 0, &m, false);
+                    game_data.analyze_position(&position, 0, &m, false, &analysis);
                 }
             }
         });
@@ -296,13 +700,20 @@ mod tests {
 
     #[bench]
     fn bench_check_other_moves(b: &mut test::Bencher) {
+        let detectors = RareMoveDetectors::default();
         b.iter(|| {
             let mut game_data = GameData::default();
             let position = Chess::default();
             // game has average of 60 moves, 20 legal moves in starting position
             for _ in 0..3 {
                 for m in position.legal_moves() {
-                    game_data.check_other_moves(&position, 0, &m, false, false);
+                    game_data.check_other_moves(
+                        &position,
+                        0,
+                        &m,
+                        PlayedMove::default(),
+                        &detectors,
+                    );
                 }
             }
         });
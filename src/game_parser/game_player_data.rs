@@ -1,10 +1,13 @@
 use pgn_reader::San;
-use shakmaty::{Chess, Move, Position, Role};
+use shakmaty::{
+    attacks,
+    fen::Fen,
+    zobrist::{Zobrist64, ZobristHash},
+    Board, Chess, Color, EnPassantMode, Move, Position, Role,
+};
 use std::cmp::min;
 
-use crate::{util::is_double_disambiguation, Error};
-
-use super::enums::{CheckType, MoveType};
+use super::{enums::MoveType, material, ForcedMateSearch, RareMoveDetectors};
 
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 #[repr(C)]
@@ -19,6 +22,22 @@ pub struct GamePlayerData {
     pub declined_en_passants: u8,
     pub double_disambiguation_checkmates: u8,
     pub double_disambiguation_capture_checkmates: u8,
+    pub underpromotions: u8,
+    pub underpromotion_only_mates: u8,
+    pub underpromotion_queen_also_mates: u8,
+    pub stalemates: u8,
+    pub stalemate_swindles: u8,
+    pub missed_stalemate_swindles: u8,
+    pub avoided_stalemates: u8,
+    pub missed_forced_mates: u8,
+    pub final_material: u8,
+    pub max_deficit: u8,
+    // largest deficit the player got back to even material from
+    pub max_recovered_deficit: u8,
+    pub plies_behind: u16,
+    // indexed by role, pawn first
+    pub max_piece_counts: [u8; 6],
+    pub promotions: u8,
     pub rare_checkmates: Vec<RareMove>,
 }
 
@@ -27,30 +46,122 @@ pub struct RareMove {
     pub san: String,
     pub ply: u16,
     pub move_type: MoveType,
+    // the position before the move
+    pub fen: String,
+    // filled in once every check of the ply is done
+    pub played: String,
+    // for a missed move, the player may have mated another way or still won
+    pub played_mates: bool,
+    pub won: bool,
+    pub position_hash: u64,
+    // from the first ply the move was available to the last, counting the opponent's plies
+    pub available_plies: u16,
+}
+
+impl RareMove {
+    pub fn new(position: &Chess, m: &Move, ply: usize, move_type: MoveType) -> Self {
+        Self {
+            san: San::from_move(position, m).to_string(),
+            ply: ply as u16,
+            move_type,
+            fen: Fen::from_position(position.clone(), EnPassantMode::Legal).to_string(),
+            played: String::new(),
+            played_mates: false,
+            won: false,
+            position_hash: position.zobrist_hash::<Zobrist64>(EnPassantMode::Legal).0,
+            available_plies: 1,
+        }
+    }
+
+    const fn last_ply(&self) -> u16 {
+        self.ply + self.available_plies - 1
+    }
+
+    fn is_repeated_by(&self, other: &Self) -> bool {
+        self.san == other.san
+            && self.move_type == other.move_type
+            && (self.last_ply() + 2 == other.ply || self.position_hash == other.position_hash)
+    }
+}
+
+// what the move actually played in the position led to
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct PlayedMove {
+    pub is_winner: bool,
+    pub is_checkmate: bool,
+    pub is_stalemate: bool,
+}
+
+impl PlayedMove {
+    pub fn new(position: &Chess, m: &Move, is_winner: bool) -> Self {
+        let mut position = position.clone();
+        position.play_unchecked(m);
+        Self {
+            is_winner,
+            is_checkmate: position.is_checkmate(),
+            is_stalemate: position.is_stalemate(),
+        }
+    }
 }
 
 impl GamePlayerData {
+    // whether the move would have stalemated instead, counted once per ply by the caller
     pub fn check_other_move(
         &mut self,
         mut position: Chess,
         possible_move: &Move,
         ply: usize,
-        is_winner: bool,
-        is_checkmate: bool,
-    ) {
+        played: PlayedMove,
+        detectors: &RareMoveDetectors,
+    ) -> bool {
         let board_copy = position.clone();
         position.play_unchecked(possible_move);
 
         if position.is_checkmate() {
-            self.missed_mates += u16::from(!is_checkmate);
-            self.missed_wins += u16::from(!is_winner);
-            if possible_move.is_en_passant() {
-                self.missed_en_passant_mates += 1;
-            }
+            self.check_missed_mate(&board_copy, possible_move, ply, played, detectors);
+            return false;
+        }
+        !played.is_stalemate && may_be_stalemate(&position) && position.is_stalemate()
+    }
 
-            if let Some(rare_move) = Self::check_rare_move(board_copy, possible_move, ply, false) {
-                self.rare_checkmates.push(rare_move);
-            }
+    fn check_missed_mate(
+        &mut self,
+        position: &Chess,
+        possible_move: &Move,
+        ply: usize,
+        played: PlayedMove,
+        detectors: &RareMoveDetectors,
+    ) {
+        self.missed_mates += u16::from(!played.is_checkmate);
+        self.missed_wins += u16::from(!played.is_winner);
+        if possible_move.is_en_passant() {
+            self.missed_en_passant_mates += 1;
+        }
+
+        self.check_rare_move(detectors, position, possible_move, ply, false);
+    }
+
+    // a stalemate saves the side behind on material and throws away the win for the side ahead
+    pub fn check_missed_stalemate(&mut self, position: &Chess) {
+        let balance = material::balance(position.board(), position.turn());
+        self.missed_stalemate_swindles = self
+            .missed_stalemate_swindles
+            .saturating_add(u8::from(balance < 0));
+        self.avoided_stalemates = self
+            .avoided_stalemates
+            .saturating_add(u8::from(balance > 0));
+    }
+
+    pub fn check_stalemate(&mut self, position: &Chess, m: &Move, ply: usize) {
+        self.stalemates += 1;
+        self.stalemate_swindles +=
+            u8::from(material::balance(position.board(), position.turn()) < 0);
+        if m.is_capture() || m.promotion().is_some() {
+            let move_type = MoveType::Stalemate {
+                is_capture: m.is_capture(),
+                promotion: m.promotion(),
+            };
+            self.add_rare_move(RareMove::new(position, m, ply, move_type));
         }
     }
 
@@ -60,60 +171,111 @@ impl GamePlayerData {
         }
     }
 
+    pub fn check_underpromotion(&mut self, position: &Chess, m: &Move, is_checkmate: bool) {
+        if m.promotion().is_none_or(|role| role == Role::Queen) {
+            return;
+        }
+        let queen_also_mates = is_checkmate && Self::queen_promotion_mates(position, m);
+        self.underpromotions += 1;
+        self.underpromotion_queen_also_mates += u8::from(queen_also_mates);
+        self.underpromotion_only_mates += u8::from(is_checkmate && !queen_also_mates);
+    }
+
+    fn queen_promotion_mates(position: &Chess, m: &Move) -> bool {
+        let Move::Normal {
+            role,
+            from,
+            capture,
+            to,
+            ..
+        } = *m
+        else {
+            return false;
+        };
+        let mut position = position.clone();
+        position.play_unchecked(&Move::Normal {
+            role,
+            from,
+            capture,
+            to,
+            promotion: Some(Role::Queen),
+        });
+        position.is_checkmate()
+    }
+
+    pub fn check_forced_mate(
+        &mut self,
+        search: &ForcedMateSearch,
+        position: &Chess,
+        m: &Move,
+        ply: usize,
+    ) {
+        let Some(forced_mate) = search.find(position) else {
+            return;
+        };
+        if search.forces_mate(position, m, forced_mate.depth) {
+            return;
+        }
+        self.missed_forced_mates += 1;
+        if self.elo >= search.log_min_elo {
+            let move_type = MoveType::ForcedMate {
+                depth: forced_mate.depth,
+            };
+            self.add_rare_move(RareMove::new(
+                position,
+                &forced_mate.first_move,
+                ply,
+                move_type,
+            ));
+        }
+    }
+
     pub fn check_rare_move(
-        position: Chess,
+        &mut self,
+        detectors: &RareMoveDetectors,
+        position: &Chess,
         m: &Move,
         ply: usize,
         was_played: bool,
-    ) -> Option<RareMove> {
-        if Self::is_interesting_move(m) {
-            let san = San::from_move(&position, m);
-            if is_double_disambiguation(&san) {
-                Some(RareMove {
-                    san: san.to_string(),
-                    ply: ply as u16,
-                    move_type: MoveType::DoubleDisambiguationCheckmate {
-                        is_capture: m.is_capture(),
-                        was_played,
-                        checkmate_type: Self::is_discovered_check(position, m),
-                    },
-                })
-            } else if m.role() == Role::King && m.is_capture() {
-                Some(RareMove {
-                    san: san.to_string(),
-                    ply: ply as u16,
-                    move_type: MoveType::KingCheckmate {
-                        is_capture: true,
-                        was_played,
-                    },
-                })
-            } else {
-                None
-            }
-        } else {
-            None
+    ) {
+        detectors
+            .detect(position, m, ply, was_played)
+            .into_iter()
+            .for_each(|rare_move| self.add_rare_move(rare_move));
+    }
+
+    // a mate left on the board for several turns, or offered again by a repetition, is one event
+    fn add_rare_move(&mut self, rare_move: RareMove) {
+        let Some(first) = self
+            .rare_checkmates
+            .iter_mut()
+            .rev()
+            .find(|first| first.is_repeated_by(&rare_move))
+        else {
+            self.rare_checkmates.push(rare_move);
+            return;
+        };
+        if first.last_ply() + 2 == rare_move.ply {
+            first.available_plies = rare_move.ply - first.ply + 1;
         }
     }
 
-    fn is_discovered_check(mut position: Chess, m: &Move) -> CheckType {
-        let sq = m.to();
-        position.play_unchecked(m);
-        let checkers = position.checkers();
-        match (checkers.contains(sq), checkers.count() > 1) {
-            (true, true) => CheckType::Double,
-            (true, false) => CheckType::Normal,
-            (false, _) => CheckType::Discovered,
+    pub fn check_piece_counts(&mut self, board: &Board, color: Color) {
+        for role in Role::ALL {
+            let count = board.by_piece(role.of(color)).count() as u8;
+            let max = &mut self.max_piece_counts[role as usize - 1];
+            *max = (*max).max(count);
         }
     }
 
-    fn is_interesting_move(m: &Move) -> bool {
-        m.role() == Role::Bishop || m.role() == Role::Knight || m.role() == Role::King
+    pub const fn max_pieces(&self, role: Role) -> u8 {
+        self.max_piece_counts[role as usize - 1]
     }
 
     pub fn set_elo(&mut self, value: &[u8]) {
         self.elo = std::str::from_utf8(value)
-            .map_err(Error::Utf8)
-            .and_then(|s| s.parse::<i16>().map_err(Error::ParseInt))
+            .ok()
+            .and_then(|s| s.parse::<i16>().ok())
             .unwrap_or(0);
     }
 
@@ -121,6 +283,47 @@ impl GamePlayerData {
         let l = min(value.len(), 20);
         self.name[..l].clone_from_slice(&value[..l]);
     }
+
+    pub fn get_name(&self) -> String {
+        String::from_utf8_lossy(&self.name)
+            .trim_end_matches('\0')
+            .to_string()
+    }
+
+    // the move played at the ply, for the rare moves available there, so a move left on the
+    // board for several turns ends up with the outcome of the last of them
+    pub fn set_played(&mut self, position: &Chess, m: &Move, ply: usize, outcome: PlayedMove) {
+        let mut found_at_ply = self
+            .rare_checkmates
+            .iter_mut()
+            .filter(|rare_move| {
+                (usize::from(rare_move.ply)..=usize::from(rare_move.last_ply())).contains(&ply)
+            })
+            .peekable();
+        if found_at_ply.peek().is_none() {
+            return;
+        }
+        let played = San::from_move(position, m).to_string();
+        found_at_ply.for_each(|rare_move| {
+            rare_move.played.clone_from(&played);
+            rare_move.played_mates = outcome.is_checkmate;
+            rare_move.won = outcome.is_winner;
+        });
+    }
+}
+
+// a stalemated king has no square to step to, checking its neighbours first skips generating
+// legal moves for nearly every position without missing pinned or blocked pieces
+fn may_be_stalemate(position: &Chess) -> bool {
+    let board = position.board();
+    let turn = position.turn();
+    let Some(king) = board.king_of(turn) else {
+        return true;
+    };
+    let occupied = board.occupied().without(king);
+    (attacks::king_attacks(king) & !board.by_color(turn))
+        .into_iter()
+        .all(|to| board.attacks_to(to, !turn, occupied).any())
 }
 
 #[cfg(test)]
@@ -130,8 +333,10 @@ mod tests {
     use std::str::FromStr;
 
     use super::*;
+    use crate::{game_parser::enums::CheckType, util::is_double_disambiguation};
     use pgn_reader::SanPlus;
-    use shakmaty::{fen::Fen, san::San, CastlingMode, Chess};
+    use rstest::rstest;
+    use shakmaty::{fen::Fen, san::San, CastlingMode, Chess, Role};
 
     type Error = Box<dyn std::error::Error>;
     type Result<T> = std::result::Result<T, Error>;
@@ -164,76 +369,226 @@ mod tests {
     }
 
     #[test]
-    fn test_is_discovered_mate() -> Result<()> {
-        let fen: Fen = "N2NQN1k/7p/7B/7R/5N2/7P/6B1/6K1 w - -".parse()?;
+    fn test_check_rare_move() -> Result<()> {
+        let fen: Fen = "1k6/2N1N3/1K6/NNN1N3/8/8/8/8 w - -".parse()?;
         let position: Chess = fen.into_position(CastlingMode::Standard)?;
 
-        let san = San::from_str("Nf8e6#")?;
+        let san = San::from_str("Ne5c6#")?;
         let m = san.to_move(&position)?;
-        let check_type = GamePlayerData::is_discovered_check(position, &m);
+        let mut game_player_data = GamePlayerData::default();
+        game_player_data.check_rare_move(&RareMoveDetectors::default(), &position, &m, 123, true);
+        let expected = RareMove {
+            san: "Ne5c6".to_string(),
+            ply: 123,
+            move_type: MoveType::DoubleDisambiguationCheckmate {
+                was_played: true,
+                is_capture: false,
+                checkmate_type: CheckType::Normal,
+            },
+            fen: "1k6/2N1N3/1K6/NNN1N3/8/8/8/8 w - - 0 1".to_string(),
+            played: String::new(),
+            played_mates: false,
+            won: false,
+            position_hash: position.zobrist_hash::<Zobrist64>(EnPassantMode::Legal).0,
+            available_plies: 1,
+        };
 
-        assert_eq!(check_type, CheckType::Discovered);
+        assert_eq!(game_player_data.rare_checkmates, vec![expected]);
         Ok(())
     }
 
     #[test]
-    fn test_is_not_discovered_mate() -> Result<()> {
-        let fen: Fen = "3NQN1k/4N1Np/7B/5B1R/7P/8/8/6K1 w - -".parse()?;
+    fn test_rare_move_dedupe() -> Result<()> {
+        let fen: Fen = "1k6/2N1N3/1K6/NNN1N3/8/8/8/8 w - -".parse()?;
         let position: Chess = fen.into_position(CastlingMode::Standard)?;
+        let m = San::from_str("Ne5c6#")?.to_move(&position)?;
+        let detectors = RareMoveDetectors::default();
 
-        let san = San::from_str("Nf7#")?;
-        let m = san.to_move(&position)?;
-        let check_type = GamePlayerData::is_discovered_check(position, &m);
-
-        assert_eq!(check_type, CheckType::Normal);
+        let mut game_player_data = GamePlayerData::default();
+        for ply in [10, 12, 14, 30] {
+            game_player_data.check_rare_move(&detectors, &position, &m, ply, false);
+        }
+        game_player_data.check_rare_move(&detectors, &position, &m, 32, true);
+        let found = game_player_data
+            .rare_checkmates
+            .iter()
+            .map(|rare_move| (rare_move.ply, rare_move.available_plies))
+            .collect::<Vec<_>>();
+
+        // the repetition at ply 30 is dropped, the played mate is its own event
+        assert_eq!(found, vec![(10, 5), (32, 1)]);
         Ok(())
     }
 
     #[test]
-    fn test_is_discovered_double_mate() -> Result<()> {
-        let fen: Fen = "3NQN1k/7p/4N2B/3N3R/7P/8/6B1/6K1 w - -".parse()?;
+    fn test_set_played_on_every_ply_of_a_merged_move() -> Result<()> {
+        let fen: Fen = "1k6/2N1N3/1K6/NNN1N3/8/8/8/8 w - -".parse()?;
         let position: Chess = fen.into_position(CastlingMode::Standard)?;
+        let m = San::from_str("Ne5c6#")?.to_move(&position)?;
+        let quiet = San::from_str("Ne7g6")?.to_move(&position)?;
+        let other_mate = San::from_str("Na5c6#")?.to_move(&position)?;
+        let detectors = RareMoveDetectors::default();
+
+        let mut game_player_data = GamePlayerData::default();
+        for (ply, played) in [(10, &quiet), (12, &other_mate)] {
+            game_player_data.check_rare_move(&detectors, &position, &m, ply, false);
+            let outcome = PlayedMove::new(&position, played, true);
+            game_player_data.set_played(&position, played, ply, outcome);
+        }
+        let rare_move = &game_player_data.rare_checkmates[0];
 
-        let san = San::from_str("Ng6#")?;
-        let m = san.to_move(&position)?;
-        let check_type = GamePlayerData::is_discovered_check(position, &m);
+        assert_eq!(rare_move.available_plies, 3);
+        assert_eq!(rare_move.played, "Nac6");
+        assert!(rare_move.played_mates);
+        Ok(())
+    }
 
-        assert_eq!(check_type, CheckType::Double);
+    #[rstest]
+    #[case("6br/5Ppk/7p/8/8/8/8/K7 w - - 0 1", "f8=N", 1, 0)]
+    #[case("1r5k/2P3pp/8/8/8/8/8/K7 w - - 0 1", "cxb8=R", 0, 1)]
+    #[case("1r5k/2P3pp/8/8/8/8/8/K7 w - - 0 1", "c8=N", 0, 0)]
+    fn test_check_underpromotion(
+        #[case] fen: &str,
+        #[case] san: &str,
+        #[case] only_mates: u8,
+        #[case] queen_also_mates: u8,
+    ) -> Result<()> {
+        let fen: Fen = fen.parse()?;
+        let position: Chess = fen.into_position(CastlingMode::Standard)?;
+        let m = San::from_str(san)?.to_move(&position)?;
+        let is_checkmate = position.clone().play(&m)?.is_checkmate();
+
+        let mut game_player_data = GamePlayerData::default();
+        game_player_data.check_underpromotion(&position, &m, is_checkmate);
+
+        assert_eq!(game_player_data.underpromotions, 1);
+        assert_eq!(game_player_data.underpromotion_only_mates, only_mates);
+        assert_eq!(
+            game_player_data.underpromotion_queen_also_mates,
+            queen_also_mates
+        );
         Ok(())
     }
 
     #[test]
-    fn test_is_normal_double_disambiguation_mate() -> Result<()> {
-        let fen: Fen = "1k6/2N1N3/1K6/NNN1N3/8/8/8/8 w - -".parse()?;
+    fn test_check_avoided_stalemate() -> Result<()> {
+        let fen: Fen = "k7/8/8/8/8/8/8/1Q5K w - - 0 1".parse()?;
         let position: Chess = fen.into_position(CastlingMode::Standard)?;
+        let m = San::from_str("Qb6")?.to_move(&position)?;
+        let detectors = RareMoveDetectors::default();
+
+        let mut game_player_data = GamePlayerData::default();
+        assert!(game_player_data.check_other_move(
+            position.clone(),
+            &m,
+            0,
+            PlayedMove::default(),
+            &detectors,
+        ));
+        game_player_data.check_missed_stalemate(&position);
+        assert_eq!(game_player_data.avoided_stalemates, 1);
+        assert_eq!(game_player_data.missed_stalemate_swindles, 0);
+
+        let played = PlayedMove {
+            is_stalemate: true,
+            ..PlayedMove::default()
+        };
+        assert!(!game_player_data.check_other_move(position, &m, 0, played, &detectors));
+        Ok(())
+    }
 
-        let san = San::from_str("Ne5c6#")?;
-        let m = san.to_move(&position)?;
-        let check_type = GamePlayerData::is_discovered_check(position, &m);
-
-        assert_eq!(check_type, CheckType::Normal);
+    #[test]
+    fn test_check_stalemate_with_pinned_piece() -> Result<()> {
+        // the knight on b7 is pinned by the bishop, Nd7 takes the last free square
+        let fen: Fen = "k7/1n6/1K6/2N5/4B3/8/8/8 w - - 0 1".parse()?;
+        let position: Chess = fen.into_position(CastlingMode::Standard)?;
+        let m = San::from_str("Nd7")?.to_move(&position)?;
+        let detectors = RareMoveDetectors::default();
+
+        let mut game_player_data = GamePlayerData::default();
+        assert!(game_player_data.check_other_move(
+            position,
+            &m,
+            0,
+            PlayedMove::default(),
+            &detectors,
+        ));
         Ok(())
     }
 
     #[test]
-    fn test_check_rare_move() -> Result<()> {
-        let fen: Fen = "1k6/2N1N3/1K6/NNN1N3/8/8/8/8 w - -".parse()?;
+    fn test_check_stalemate_swindle() -> Result<()> {
+        let fen: Fen = "k7/8/1r5p/7p/7p/7p/7p/1Q5K w - - 0 1".parse()?;
         let position: Chess = fen.into_position(CastlingMode::Standard)?;
+        let m = San::from_str("Qxb6")?.to_move(&position)?;
 
-        let san = San::from_str("Ne5c6#")?;
-        let m = san.to_move(&position)?;
-        let rare_move = GamePlayerData::check_rare_move(position, &m, 123, true);
+        let mut game_player_data = GamePlayerData::default();
+        game_player_data.check_stalemate(&position, &m, 80);
+        let outcome = PlayedMove::new(&position, &m, false);
+        game_player_data.set_played(&position, &m, 80, outcome);
         let expected = RareMove {
-            san: "Ne5c6".to_string(),
-            ply: 123,
-            move_type: MoveType::DoubleDisambiguationCheckmate {
-                was_played: true,
-                is_capture: false,
-                checkmate_type: CheckType::Normal,
+            san: "Qxb6".to_string(),
+            ply: 80,
+            move_type: MoveType::Stalemate {
+                is_capture: true,
+                promotion: None,
             },
+            fen: "k7/8/1r5p/7p/7p/7p/7p/1Q5K w - - 0 1".to_string(),
+            played: "Qxb6".to_string(),
+            played_mates: false,
+            won: false,
+            position_hash: position.zobrist_hash::<Zobrist64>(EnPassantMode::Legal).0,
+            available_plies: 1,
+        };
+
+        assert!(position.play(&m)?.is_stalemate());
+        assert_eq!(
+            (
+                game_player_data.stalemates,
+                game_player_data.stalemate_swindles
+            ),
+            (1, 1)
+        );
+        assert_eq!(game_player_data.rare_checkmates, vec![expected]);
+        assert_eq!(
+            game_player_data.rare_checkmates[0].move_type.to_string(),
+            "SMx  "
+        );
+        Ok(())
+    }
+
+    #[rstest]
+    #[case("Kf1", 1, "M2  ?")]
+    #[case("Qg8", 0, "")]
+    fn test_check_forced_mate(
+        #[case] san: &str,
+        #[case] missed: u8,
+        #[case] display: &str,
+    ) -> Result<()> {
+        let fen: Fen = "3r3k/6pp/7N/8/2Q5/8/8/6K1 w - - 0 1".parse()?;
+        let position: Chess = fen.into_position(CastlingMode::Standard)?;
+        let m = San::from_str(san)?.to_move(&position)?;
+        let search = ForcedMateSearch {
+            max_depth: 2,
+            max_forcing_moves: 8,
+            log_min_elo: 2000,
         };
 
-        assert_eq!(rare_move, Some(expected));
+        let mut game_player_data = GamePlayerData {
+            elo: 2100,
+            ..Default::default()
+        };
+        game_player_data.check_forced_mate(&search, &position, &m, 30);
+
+        assert_eq!(game_player_data.missed_forced_mates, missed);
+        assert_eq!(
+            game_player_data
+                .rare_checkmates
+                .iter()
+                .map(|rare_move| rare_move.move_type.to_string())
+                .collect::<String>(),
+            display
+        );
         Ok(())
     }
 
@@ -241,11 +596,18 @@ mod tests {
     fn bench_check_other_move(b: &mut test::Bencher) {
         b.iter(|| {
             let mut game_player_data = GamePlayerData::default();
+            let detectors = RareMoveDetectors::default();
             let position = Chess::default();
             // game has average of 2000 move variations, 20 legal moves in starting position
             for _ in 0..100 {
                 for m in position.legal_moves() {
-                    game_player_data.check_other_move(position.clone(), &m, 0, false, false);
+                    game_player_data.check_other_move(
+                        position.clone(),
+                        &m,
+                        0,
+                        PlayedMove::default(),
+                        &detectors,
+                    );
                 }
             }
         });
@@ -253,12 +615,14 @@ mod tests {
 
     #[bench]
     fn bench_check_rare_move(b: &mut test::Bencher) {
+        let detectors = RareMoveDetectors::default();
         b.iter(|| {
+            let mut game_player_data = GamePlayerData::default();
             let position = Chess::default();
             // game has average of 2000 move variations, 20 legal moves in starting position
             for _ in 0..100 {
                 for m in position.legal_moves() {
-                    GamePlayerData::check_rare_move(position.clone(), &m, 0, false);
+                    game_player_data.check_rare_move(&detectors, &position, &m, 0, false);
                 }
             }
         });
@@ -268,8 +632,9 @@ mod tests {
     fn bench_check_rare_move_single_none(b: &mut test::Bencher) {
         let position = Chess::default();
         let m = position.legal_moves()[0].clone();
+        let detectors = RareMoveDetectors::default();
         b.iter(|| {
-            GamePlayerData::check_rare_move(position.clone(), &m, 0, false);
+            GamePlayerData::default().check_rare_move(&detectors, &position, &m, 0, false);
         });
     }
 
@@ -280,8 +645,9 @@ mod tests {
         let san = San::from_str("Nc3")?;
         let m = san.to_move(&position)?;
 
+        let detectors = RareMoveDetectors::default();
         b.iter(|| {
-            GamePlayerData::check_rare_move(position.clone(), &m, 0, false);
+            GamePlayerData::default().check_rare_move(&detectors, &position, &m, 0, false);
         });
         Ok(())
     }
@@ -294,8 +660,9 @@ mod tests {
         let san = San::from_str("Nf8e6#")?;
         let m = san.to_move(&position)?;
 
+        let detectors = RareMoveDetectors::default();
         b.iter(|| {
-            GamePlayerData::check_rare_move(position.clone(), &m, 0, false);
+            GamePlayerData::default().check_rare_move(&detectors, &position, &m, 0, false);
         });
         Ok(())
     }
@@ -0,0 +1,274 @@
+use std::fmt::Display;
+
+use shakmaty::{attacks, Bitboard, Board, Chess, Color, File, Position, Rank, Role, Square};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MatePattern {
+    Smothered,
+    BackRank,
+    Corridor,
+    Anastasia,
+    Arabian,
+    Epaulette,
+    Boden,
+    DoubleBishop,
+}
+
+impl MatePattern {
+    pub const ALL: [Self; 8] = [
+        Self::Smothered,
+        Self::BackRank,
+        Self::Corridor,
+        Self::Anastasia,
+        Self::Arabian,
+        Self::Epaulette,
+        Self::Boden,
+        Self::DoubleBishop,
+    ];
+
+    fn matches(self, mate: &Mate) -> bool {
+        match self {
+            Self::Smothered => mate.is_smothered(),
+            Self::BackRank => mate.is_back_rank(),
+            Self::Corridor => mate.is_corridor(),
+            Self::Anastasia => mate.is_anastasia(),
+            Self::Arabian => mate.is_arabian(),
+            Self::Epaulette => mate.is_epaulette(),
+            Self::Boden => mate.is_bishop_pair(false),
+            Self::DoubleBishop => mate.is_bishop_pair(true),
+        }
+    }
+}
+
+impl Display for MatePattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::Smothered => "smothered",
+            Self::BackRank => "back_rank",
+            Self::Corridor => "corridor",
+            Self::Anastasia => "anastasia",
+            Self::Arabian => "arabian",
+            Self::Epaulette => "epaulette",
+            Self::Boden => "boden",
+            Self::DoubleBishop => "double_bishop",
+        };
+        write!(f, "{s}")
+    }
+}
+
+// one bit per MatePattern, so GameData keeps a fixed layout
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct MatePatterns(u16);
+
+impl MatePatterns {
+    pub fn classify(position: &Chess) -> Self {
+        Mate::new(position).map_or_else(Self::default, |mate| {
+            MatePattern::ALL
+                .into_iter()
+                .filter(|pattern| pattern.matches(&mate))
+                .collect()
+        })
+    }
+
+    pub const fn contains(self, pattern: MatePattern) -> bool {
+        self.0 & (1 << pattern as u16) != 0
+    }
+
+    pub fn patterns(self) -> impl Iterator<Item = MatePattern> {
+        MatePattern::ALL
+            .into_iter()
+            .filter(move |&pattern| self.contains(pattern))
+    }
+}
+
+impl FromIterator<MatePattern> for MatePatterns {
+    fn from_iter<T: IntoIterator<Item = MatePattern>>(iter: T) -> Self {
+        Self(
+            iter.into_iter()
+                .fold(0, |bits, pattern| bits | (1 << pattern as u16)),
+        )
+    }
+}
+
+// the final position seen from the mated side
+struct Mate<'a> {
+    board: &'a Board,
+    defender: Color,
+    king: Square,
+    checker: Square,
+    checker_role: Role,
+}
+
+impl<'a> Mate<'a> {
+    fn new(position: &'a Chess) -> Option<Self> {
+        if !position.is_checkmate() {
+            return None;
+        }
+        let board = position.board();
+        let defender = position.turn();
+        let king = board.king_of(defender)?;
+        // double checks have no single mating piece to match patterns against
+        let checker = position.checkers().single_square()?;
+        let checker_role = board.role_at(checker)?;
+        Some(Self {
+            board,
+            defender,
+            king,
+            checker,
+            checker_role,
+        })
+    }
+
+    fn neighbors(&self) -> Bitboard {
+        attacks::king_attacks(self.king)
+    }
+
+    fn is_own(&self, squares: Bitboard) -> bool {
+        !squares.is_empty() && (squares & !self.board.by_color(self.defender)).is_empty()
+    }
+
+    fn attackers(&self, role: Role) -> Bitboard {
+        self.board.by_piece(role.of(!self.defender))
+    }
+
+    fn is_line_check(&self) -> bool {
+        matches!(self.checker_role, Role::Rook | Role::Queen)
+            && (self.checker.file() == self.king.file() || self.checker.rank() == self.king.rank())
+    }
+
+    // the king's neighbors across the line of the check
+    fn flanks(&self) -> Bitboard {
+        let across = if self.checker.file() == self.king.file() {
+            Bitboard::from_rank(self.king.rank())
+        } else {
+            Bitboard::from_file(self.king.file())
+        };
+        self.neighbors() & across
+    }
+
+    fn is_smothered(&self) -> bool {
+        self.checker_role == Role::Knight && self.is_own(self.neighbors())
+    }
+
+    fn is_back_rank(&self) -> bool {
+        let back_rank = self.defender.fold_wb(Rank::First, Rank::Eighth);
+        let in_front = self.neighbors() & !Bitboard::from_rank(back_rank);
+        self.is_line_check()
+            && self.king.rank() == back_rank
+            && self.checker.rank() == back_rank
+            && self.is_own(in_front)
+    }
+
+    fn is_corridor(&self) -> bool {
+        self.is_line_check() && !self.is_back_rank() && self.is_own(self.flanks())
+    }
+
+    fn is_anastasia(&self) -> bool {
+        let inward = self.neighbors() & Bitboard::from_rank(self.king.rank());
+        let knight_cover = self
+            .attackers(Role::Knight)
+            .into_iter()
+            .any(|knight| !(attacks::knight_attacks(knight) & self.neighbors()).is_empty());
+        self.is_line_check()
+            && matches!(self.king.file(), File::A | File::H)
+            && self.checker.file() == self.king.file()
+            && self.is_own(inward)
+            && knight_cover
+    }
+
+    fn is_arabian(&self) -> bool {
+        let is_corner = self.neighbors().count() == 3;
+        let rook_defended = self
+            .attackers(Role::Knight)
+            .into_iter()
+            .any(|knight| attacks::knight_attacks(knight).contains(self.checker));
+        is_corner
+            && self.checker_role == Role::Rook
+            && self.checker.distance(self.king) == 1
+            && rook_defended
+    }
+
+    fn is_epaulette(&self) -> bool {
+        let flanks = self.flanks();
+        self.checker_role == Role::Queen
+            && self.checker.file() == self.king.file()
+            && self.checker.distance(self.king) == 2
+            && flanks.count() == 2
+            && self.is_own(flanks)
+    }
+
+    // Boden: the second bishop covers the king along a crossing diagonal,
+    // double bishop: along a parallel one
+    fn is_bishop_pair(&self, parallel: bool) -> bool {
+        if self.checker_role != Role::Bishop {
+            return false;
+        }
+        let check_diagonal = is_rising_diagonal(self.checker, self.king);
+        self.attackers(Role::Bishop)
+            .into_iter()
+            .filter(|&bishop| bishop != self.checker)
+            .filter_map(|bishop| {
+                let covered = (self.board.attacks_from(bishop) & self.neighbors()).first()?;
+                Some(is_rising_diagonal(bishop, covered))
+            })
+            .any(|diagonal| (diagonal == check_diagonal) == parallel)
+    }
+}
+
+// a1-h8 direction, as opposed to a8-h1
+fn is_rising_diagonal(a: Square, b: Square) -> bool {
+    let files = a.file() as i32 - b.file() as i32;
+    let ranks = a.rank() as i32 - b.rank() as i32;
+    files * ranks > 0
+}
+
+#[cfg(test)]
+#[allow(clippy::panic_in_result_fn)]
+mod tests {
+    use rstest::rstest;
+    use shakmaty::{fen::Fen, CastlingMode};
+
+    use super::*;
+
+    type Error = Box<dyn std::error::Error>;
+    type Result<T> = std::result::Result<T, Error>;
+
+    #[rstest]
+    #[case("6rk/5Npp/8/8/8/8/8/6K1 b - - 0 1", MatePattern::Smothered)]
+    #[case("3R2k1/5ppp/8/8/8/8/8/6K1 b - - 0 1", MatePattern::BackRank)]
+    #[case("8/4N3/7p/R6k/6pp/8/8/6K1 b - - 0 1", MatePattern::Corridor)]
+    #[case("8/4N1pk/8/7R/8/8/8/6K1 b - - 0 1", MatePattern::Anastasia)]
+    #[case("7k/7R/5N2/8/8/8/8/6K1 b - - 0 1", MatePattern::Arabian)]
+    #[case("3rkr2/8/4Q3/8/8/8/8/6K1 b - - 0 1", MatePattern::Epaulette)]
+    #[case("2kr4/3p4/B7/8/5B2/8/8/6K1 b - - 0 1", MatePattern::Boden)]
+    #[case("7k/7p/8/8/2B5/8/1B6/6K1 b - - 0 1", MatePattern::DoubleBishop)]
+    fn test_classify(#[case] fen: &str, #[case] pattern: MatePattern) -> Result<()> {
+        let fen: Fen = fen.parse()?;
+        let position: Chess = fen.into_position(CastlingMode::Standard)?;
+        let patterns = MatePatterns::classify(&position);
+
+        assert!(position.is_checkmate());
+        assert!(patterns.contains(pattern), "{pattern} not in {patterns:?}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_classify_no_mate() {
+        assert_eq!(
+            MatePatterns::classify(&Chess::default()),
+            MatePatterns::default()
+        );
+    }
+
+    #[test]
+    fn test_patterns_roundtrip() {
+        let patterns: MatePatterns = [MatePattern::BackRank, MatePattern::Boden]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            patterns.patterns().collect::<Vec<_>>(),
+            vec![MatePattern::BackRank, MatePattern::Boden]
+        );
+    }
+}
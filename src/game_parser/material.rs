@@ -0,0 +1,157 @@
+use shakmaty::{Board, ByColor, Chess, Color, Position, Role};
+
+use super::GameData;
+
+pub const fn value(role: Role) -> i32 {
+    match role {
+        Role::Pawn => 1,
+        Role::Knight | Role::Bishop => 3,
+        Role::Rook => 5,
+        Role::Queen => 9,
+        Role::King => 0,
+    }
+}
+
+pub fn material(board: &Board, color: Color) -> i32 {
+    Role::ALL
+        .into_iter()
+        .map(|role| value(role) * board.by_piece(role.of(color)).count() as i32)
+        .sum()
+}
+
+// positive when color is ahead
+pub fn balance(board: &Board, color: Color) -> i32 {
+    material(board, color) - material(board, !color)
+}
+
+// a trade leaves one side behind for a single ply, so a deficit only counts once it has
+// lasted two plies
+fn settle(previous: i32, balance: i32) -> i32 {
+    if previous < 0 && balance < 0 {
+        previous.max(balance)
+    } else if previous > 0 && balance > 0 {
+        previous.min(balance)
+    } else {
+        0
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Deficit {
+    current: i32,
+    max: i32,
+    recovered: i32,
+    plies: u16,
+}
+
+impl Deficit {
+    fn update(&mut self, deficit: i32) {
+        if deficit > 0 {
+            self.plies += 1;
+            self.current = self.current.max(deficit);
+            self.max = self.max.max(deficit);
+        } else {
+            self.recovered = self.recovered.max(self.current);
+            self.current = 0;
+        }
+    }
+}
+
+// follows the material balance of a game as it is replayed
+#[derive(Debug, Default)]
+pub struct MaterialTracker {
+    last_balance: i32,
+    deficits: ByColor<Deficit>,
+}
+
+impl MaterialTracker {
+    pub fn new(start_position: &Chess) -> Self {
+        Self {
+            last_balance: balance(start_position.board(), Color::White),
+            deficits: ByColor::default(),
+        }
+    }
+
+    pub fn update(&mut self, position: &Chess) {
+        let white_balance = balance(position.board(), Color::White);
+        let settled = settle(self.last_balance, white_balance);
+        self.last_balance = white_balance;
+        self.deficits.white.update(-settled);
+        self.deficits.black.update(settled);
+    }
+
+    pub fn finish(&self, game_data: &mut GameData, position: &Chess) {
+        for color in Color::ALL {
+            let deficit = self.deficits.get(color);
+            let player_data =
+                color.fold_wb(&mut game_data.white_player, &mut game_data.black_player);
+            player_data.final_material = material(position.board(), color) as u8;
+            player_data.max_deficit = deficit.max as u8;
+            player_data.max_recovered_deficit = deficit.recovered as u8;
+            player_data.plies_behind = deficit.plies;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shakmaty::{fen::Fen, CastlingMode};
+
+    use super::*;
+
+    type Error = Box<dyn std::error::Error>;
+    type Result<T> = std::result::Result<T, Error>;
+
+    const BLACK_DOWN_A_QUEEN: &str = "rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    #[test]
+    fn test_material() {
+        let position = Chess::default();
+        assert_eq!(material(position.board(), Color::White), 39);
+        assert_eq!(balance(position.board(), Color::Black), 0);
+    }
+
+    fn track(positions: &[&Chess]) -> GameData {
+        let mut tracker = MaterialTracker::new(positions[0]);
+        for position in positions {
+            tracker.update(position);
+        }
+        let mut game_data = GameData::default();
+        tracker.finish(&mut game_data, positions[positions.len() - 1]);
+        game_data
+    }
+
+    #[test]
+    #[allow(clippy::panic_in_result_fn)]
+    fn test_material_tracker() -> Result<()> {
+        let fen: Fen = BLACK_DOWN_A_QUEEN.parse()?;
+        let down: Chess = fen.into_position(CastlingMode::Standard)?;
+        let equal = Chess::default();
+
+        let trade = track(&[&equal, &down, &equal]);
+        assert_eq!(trade.black_player.max_deficit, 0);
+
+        let comeback = track(&[&equal, &down, &down, &down, &equal]);
+        let black = &comeback.black_player;
+        assert_eq!(
+            (
+                black.max_deficit,
+                black.max_recovered_deficit,
+                black.plies_behind,
+                comeback.white_player.plies_behind,
+                black.final_material,
+            ),
+            (9, 9, 2, 0, 39)
+        );
+
+        let lost = track(&[&equal, &down, &down]);
+        assert_eq!(
+            (
+                lost.black_player.max_recovered_deficit,
+                lost.black_player.final_material
+            ),
+            (0, 30)
+        );
+        Ok(())
+    }
+}
@@ -1,8 +1,11 @@
-use crate::error::Result;
-use crate::game_parser::{Game, GameData};
+use crate::error::{Error, Source};
+use crate::game_parser::{FailureReason, Game, GameFailure};
 use pgn_reader::{RawHeader, SanPlus, Skip, Visitor};
 use std::collections::HashMap;
 use std::mem;
+use std::str::Utf8Error;
+
+type HeaderResult = (&'static str, FailureReason, Result<(), Source>);
 
 pub struct FirstMove {
     pub count: u64,
@@ -19,7 +22,7 @@ impl FirstMove {
         }
     }
 
-    fn update(&mut self, game_link: [u8; 8], start_time: u32) -> Result<()> {
+    fn update(&mut self, game_link: [u8; 8], start_time: u32) -> Result<(), Utf8Error> {
         self.count += 1;
         if start_time < self.first_played {
             self.game_link = std::str::from_utf8(&game_link)?.to_string();
@@ -37,10 +40,34 @@ impl FirstMove {
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    UnsupportedVariant,
+}
+
+#[derive(Debug)]
+pub enum ParsedGame {
+    Parsed(Box<Game>),
+    Skipped(SkipReason),
+    Malformed(GameFailure),
+}
+
+impl ParsedGame {
+    pub fn into_game(self) -> Option<Game> {
+        match self {
+            Self::Parsed(game) => Some(*game),
+            Self::Skipped(_) | Self::Malformed(_) => None,
+        }
+    }
+}
+
 pub struct Validator {
     games: i64,
     pub move_counter: HashMap<SanPlus, FirstMove>,
+    keep_headers: bool,
     game: Game,
+    skip: Option<SkipReason>,
+    failure: Option<(FailureReason, Error)>,
 }
 
 impl Validator {
@@ -48,60 +75,104 @@ impl Validator {
         Self {
             games: 0,
             move_counter: HashMap::new(),
-            game: Game {
-                success: true,
-                ..Default::default()
-            },
+            keep_headers: false,
+            game: Game::default(),
+            skip: None,
+            failure: None,
+        }
+    }
+
+    // every header is kept as written, so the game can be written back as pgn
+    pub const fn keeping_headers(mut self, keep_headers: bool) -> Self {
+        self.keep_headers = keep_headers;
+        self
+    }
+
+    fn fail(&mut self, reason: FailureReason, error: Error) {
+        self.failure.get_or_insert((reason, error));
+    }
+
+    fn parse_header(&mut self, key: &[u8], v: &[u8]) -> Option<HeaderResult> {
+        let game_data = &mut self.game.data;
+        Some(match key {
+            b"Event" => (
+                "Event",
+                FailureReason::BadEvent,
+                game_data.parse_time_control(v),
+            ),
+            b"Result" => (
+                "Result",
+                FailureReason::BadResult,
+                game_data.parse_result(v),
+            ),
+            b"Termination" => (
+                "Termination",
+                FailureReason::UnknownTermination,
+                game_data.parse_termination(v),
+            ),
+            b"TimeControl" => (
+                "TimeControl",
+                FailureReason::BadTimeControl,
+                game_data.parse_clock(v),
+            ),
+            b"FEN" => ("FEN", FailureReason::BadFen, self.game.parse_fen(v)),
+            b"UTCDate" => ("UTCDate", FailureReason::BadDate, game_data.parse_date(v)),
+            b"UTCTime" => ("UTCTime", FailureReason::BadTime, game_data.parse_time(v)),
+            _ => return None,
+        })
+    }
+
+    fn check_variant(&mut self, value: &[u8]) {
+        if !matches!(value, b"Standard" | b"From Position") {
+            self.skip = Some(SkipReason::UnsupportedVariant);
         }
     }
 }
 
 impl Visitor for Validator {
-    type Result = Game;
+    type Result = ParsedGame;
 
     fn begin_game(&mut self) {
         self.games += 1;
     }
 
     fn header(&mut self, key: &[u8], value: RawHeader<'_>) {
-        let game_data = &mut self.game.data;
         let v = value.as_bytes();
+        if self.keep_headers {
+            self.game.headers.push((
+                String::from_utf8_lossy(key).into_owned(),
+                String::from_utf8_lossy(v).into_owned(),
+            ));
+        }
+        let game_data = &mut self.game.data;
         match key {
             b"WhiteElo" => game_data.white_player.set_elo(v),
             b"BlackElo" => game_data.black_player.set_elo(v),
             b"White" => game_data.white_player.set_name(v),
             b"Black" => game_data.black_player.set_name(v),
-            b"Event" => game_data
-                .parse_time_control(v)
-                .unwrap_or_else(|_| self.game.success = false),
-            b"Result" => game_data
-                .parse_result(v)
-                .unwrap_or_else(|_| self.game.success = false),
-            b"Termination" => game_data
-                .parse_termination(v)
-                .unwrap_or_else(|_| self.game.success = false),
             b"Site" => game_data.parse_site(v),
-            b"UTCDate" => game_data
-                .parse_date(v)
-                .unwrap_or_else(|_| self.game.success = false),
-            b"UTCTime" => game_data
-                .parse_time(v)
-                .unwrap_or_else(|_| self.game.success = false),
-            _ => {}
+            b"Variant" => self.check_variant(v),
+            _ => {
+                if let Some((name, reason, Err(source))) = self.parse_header(key, v) {
+                    self.fail(reason, Error::header(name, source));
+                }
+            }
         }
     }
 
     fn end_headers(&mut self) -> Skip {
-        Skip(!self.game.success)
+        Skip(self.skip.is_some() || self.failure.is_some())
     }
 
     fn san(&mut self, san_plus: SanPlus) {
-        if self.game.success {
+        if self.failure.is_none() {
             self.move_counter
                 .entry(san_plus.clone())
                 .or_insert(FirstMove::new())
                 .update(self.game.data.game_link, self.game.data.start_time)
-                .unwrap_or_else(|_| self.game.success = false);
+                .unwrap_or_else(|err| {
+                    self.fail(FailureReason::BadGameLink, Error::header("Site", err));
+                });
             self.game.sans.push(san_plus.san);
         }
     }
@@ -111,13 +182,20 @@ impl Visitor for Validator {
     }
 
     fn end_game(&mut self) -> Self::Result {
-        mem::replace(
+        let game = mem::replace(
             &mut self.game,
             Game {
                 sans: Vec::with_capacity(80),
-                success: true,
-                data: GameData::default(),
+                ..Default::default()
             },
-        )
+        );
+        match (self.skip.take(), self.failure.take()) {
+            (Some(reason), _) => ParsedGame::Skipped(reason),
+            (None, Some((reason, error))) => ParsedGame::Malformed(GameFailure::new(
+                reason,
+                error.in_game(&game.data.get_game_id()),
+            )),
+            (None, None) => ParsedGame::Parsed(Box::new(game)),
+        }
     }
 }
@@ -31,17 +31,27 @@
 
 use rstest as _;
 
+mod catalogue;
 mod config;
 mod error;
 mod game_parser;
+mod openings;
 mod parser;
 mod plotter;
+mod records;
 mod ui;
 mod util;
+mod verify;
 
 pub use error::{Error, Result};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    parser::run_all_files().await
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.split_first() {
+        Some((command, sans)) if command == "openings" => openings::query(sans),
+        Some((command, paths)) if command == "catalogue" => catalogue::run(paths),
+        Some((command, paths)) if command == "verify" => verify::run(paths),
+        _ => parser::run_all_files().await,
+    }
 }
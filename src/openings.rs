@@ -0,0 +1,502 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    io::{self, Write},
+};
+
+use chrono::DateTime;
+use serde::Serialize;
+use shakmaty::san::San;
+
+use crate::{
+    error::{Cause, Error, Source},
+    game_parser::{GameData, GameResult},
+    util::read_openings,
+    Result,
+};
+
+const MAGIC: &[u8; 4] = b"OPTR";
+const VERSION: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FirstPlayed {
+    pub start_time: u32,
+    pub game_link: [u8; 8],
+}
+
+impl FirstPlayed {
+    fn date(self) -> String {
+        DateTime::from_timestamp(i64::from(self.start_time), 0)
+            .map(|datetime| datetime.date_naive().to_string())
+            .unwrap_or_default()
+    }
+
+    fn link(self) -> String {
+        format!(
+            "https://lichess.org/{}",
+            String::from_utf8_lossy(&self.game_link)
+        )
+    }
+}
+
+// the games that reached a node, whatever came after
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OpeningNode {
+    pub games: u64,
+    pub white_wins: u64,
+    pub draws: u64,
+    pub black_wins: u64,
+    // both ratings of every game where the two are known
+    elo_sum: u64,
+    rated_games: u64,
+    pub first_played: Option<FirstPlayed>,
+    pub children: BTreeMap<String, Self>,
+}
+
+impl OpeningNode {
+    fn add(&mut self, game_data: &GameData) {
+        self.games += 1;
+        match game_data.result {
+            GameResult::WhiteWin => self.white_wins += 1,
+            GameResult::BlackWin => self.black_wins += 1,
+            GameResult::Draw => self.draws += 1,
+            GameResult::Unfinished => {}
+        }
+        let (white, black) = (game_data.white_player.elo, game_data.black_player.elo);
+        if white > 0 && black > 0 {
+            self.elo_sum += (white as u64) + (black as u64);
+            self.rated_games += 1;
+        }
+        self.update_first(FirstPlayed {
+            start_time: game_data.start_time,
+            game_link: game_data.game_link,
+        });
+    }
+
+    fn update_first(&mut self, first: FirstPlayed) {
+        if self
+            .first_played
+            .is_none_or(|current| first.start_time < current.start_time)
+        {
+            self.first_played = Some(first);
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.games += other.games;
+        self.white_wins += other.white_wins;
+        self.draws += other.draws;
+        self.black_wins += other.black_wins;
+        self.elo_sum += other.elo_sum;
+        self.rated_games += other.rated_games;
+        if let Some(first) = other.first_played {
+            self.update_first(first);
+        }
+        for (san, child) in other.children {
+            self.children.entry(san).or_default().merge(child);
+        }
+    }
+
+    pub const fn average_elo(&self) -> Option<u64> {
+        match self.rated_games {
+            0 => None,
+            rated_games => Some(self.elo_sum / (2 * rated_games)),
+        }
+    }
+
+    // most played first
+    pub fn moves(&self) -> Vec<(&str, &Self)> {
+        let mut moves = self
+            .children
+            .iter()
+            .map(|(san, child)| (san.as_str(), child))
+            .collect::<Vec<_>>();
+        moves.sort_by_key(|(_, child)| Reverse(child.games));
+        moves
+    }
+
+    fn write_binary(&self, w: &mut impl Write) -> io::Result<()> {
+        [
+            self.games,
+            self.white_wins,
+            self.draws,
+            self.black_wins,
+            self.elo_sum,
+            self.rated_games,
+        ]
+        .into_iter()
+        .try_for_each(|value| write_varint(w, value))?;
+        match self.first_played {
+            Some(first) => {
+                w.write_all(&[1])?;
+                write_varint(w, u64::from(first.start_time))?;
+                w.write_all(&first.game_link)?;
+            }
+            None => w.write_all(&[0])?,
+        }
+        write_varint(w, self.children.len() as u64)?;
+        self.children.iter().try_for_each(|(san, child)| {
+            w.write_all(&[san.len() as u8])?;
+            w.write_all(san.as_bytes())?;
+            child.write_binary(w)
+        })
+    }
+
+    fn read_binary(r: &mut Reader<'_>) -> std::result::Result<Self, Source> {
+        let mut node = Self {
+            games: r.varint()?,
+            white_wins: r.varint()?,
+            draws: r.varint()?,
+            black_wins: r.varint()?,
+            elo_sum: r.varint()?,
+            rated_games: r.varint()?,
+            ..Default::default()
+        };
+        if r.byte()? == 1 {
+            node.first_played = Some(FirstPlayed {
+                start_time: u32::try_from(r.varint()?)?,
+                game_link: r.take(8)?.try_into()?,
+            });
+        }
+        for _ in 0..r.varint()? {
+            let len = r.byte()?;
+            let san = std::str::from_utf8(r.take(usize::from(len))?)?.to_string();
+            node.children.insert(san, Self::read_binary(r)?);
+        }
+        Ok(node)
+    }
+}
+
+// games, result split in percent, average elo and the first game
+impl Display for OpeningNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let percent = |count: u64| count as f64 * 100.0 / self.games.max(1) as f64;
+        write!(
+            f,
+            "{:>10} games  {:5.1}% / {:5.1}% / {:5.1}%",
+            self.games,
+            percent(self.white_wins),
+            percent(self.draws),
+            percent(self.black_wins),
+        )?;
+        if let Some(elo) = self.average_elo() {
+            write!(f, "  elo {elo:>4}")?;
+        }
+        if let Some(first) = self.first_played {
+            write!(f, "  first {} {}", first.date(), first.link())?;
+        }
+        Ok(())
+    }
+}
+
+// the first plies of every game, merged move by move
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpeningTree {
+    plies: usize,
+    // labels of the files whose games are in the tree, a file is never added twice
+    files: BTreeSet<String>,
+    root: OpeningNode,
+}
+
+impl OpeningTree {
+    pub fn new(plies: usize) -> Self {
+        Self {
+            plies,
+            files: BTreeSet::new(),
+            root: OpeningNode::default(),
+        }
+    }
+
+    pub fn with_file(mut self, label: &str) -> Self {
+        self.files.insert(label.to_string());
+        self
+    }
+
+    pub fn contains_file(&self, label: &str) -> bool {
+        self.files.contains(label)
+    }
+
+    pub const fn plies(&self) -> usize {
+        self.plies
+    }
+
+    pub fn add_game(&mut self, game_data: &GameData, sans: &[San]) {
+        let mut node = &mut self.root;
+        node.add(game_data);
+        for san in sans.iter().take(self.plies) {
+            node = node.children.entry(san.to_string()).or_default();
+            node.add(game_data);
+        }
+    }
+
+    pub fn merge(&mut self, other: Self) {
+        self.files.extend(other.files);
+        self.root.merge(other.root);
+    }
+
+    pub fn find<'a>(&self, sans: impl IntoIterator<Item = &'a str>) -> Option<&OpeningNode> {
+        sans.into_iter()
+            .try_fold(&self.root, |node, san| node.children.get(san))
+    }
+
+    pub fn write_binary(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&[VERSION])?;
+        write_varint(w, self.plies as u64)?;
+        write_varint(w, self.files.len() as u64)?;
+        self.files.iter().try_for_each(|label| {
+            write_varint(w, label.len() as u64)?;
+            w.write_all(label.as_bytes())
+        })?;
+        self.root.write_binary(w)
+    }
+
+    pub fn read_binary(bytes: &[u8]) -> std::result::Result<Self, Source> {
+        let mut r = Reader { bytes };
+        if r.take(MAGIC.len())? != MAGIC || r.byte()? != VERSION {
+            return Err(Cause::UnexpectedValue("opening tree header".to_string()).into());
+        }
+        let plies = usize::try_from(r.varint()?)?;
+        let files = (0..r.varint()?)
+            .map(|_| {
+                let len = usize::try_from(r.varint()?)?;
+                Ok(std::str::from_utf8(r.take(len)?)?.to_string())
+            })
+            .collect::<std::result::Result<_, Source>>()?;
+        Ok(Self {
+            plies,
+            files,
+            root: OpeningNode::read_binary(&mut r)?,
+        })
+    }
+
+    // moves played in fewer games are left out, the binary export keeps everything
+    pub fn write_json(&self, w: &mut impl Write, min_games: u64) -> serde_json::Result<()> {
+        serde_json::to_writer(w, &JsonNode::new(&self.root, min_games))
+    }
+}
+
+#[derive(Serialize)]
+struct JsonNode<'a> {
+    games: u64,
+    white_wins: u64,
+    draws: u64,
+    black_wins: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    average_elo: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    first_played: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    game_link: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    children: BTreeMap<&'a str, Self>,
+}
+
+impl<'a> JsonNode<'a> {
+    fn new(node: &'a OpeningNode, min_games: u64) -> Self {
+        Self {
+            games: node.games,
+            white_wins: node.white_wins,
+            draws: node.draws,
+            black_wins: node.black_wins,
+            average_elo: node.average_elo(),
+            first_played: node.first_played.map(FirstPlayed::date),
+            game_link: node.first_played.map(FirstPlayed::link),
+            children: node
+                .children
+                .iter()
+                .filter(|(_, child)| child.games >= min_games)
+                .map(|(san, child)| (san.as_str(), Self::new(child, min_games)))
+                .collect(),
+        }
+    }
+}
+
+// unsigned LEB128, most counts fit in one or two bytes
+fn write_varint(w: &mut impl Write, mut value: u64) -> io::Result<()> {
+    while value >= 0x80 {
+        w.write_all(&[(value as u8) | 0x80])?;
+        value >>= 7;
+    }
+    w.write_all(&[value as u8])
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> std::result::Result<&'a [u8], Source> {
+        if self.bytes.len() < len {
+            return Err(Cause::UnexpectedValue("truncated opening tree".to_string()).into());
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> std::result::Result<u8, Source> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> std::result::Result<u64, Source> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(Cause::UnexpectedValue("varint too long".to_string()).into())
+    }
+}
+
+// `openings e4 c5 Nf3` prints the position after the moves and every continuation
+pub fn query(sans: &[String]) -> Result<()> {
+    let tree = OpeningTree::read_binary(&read_openings()?).map_err(Error::decode)?;
+    let mut out = io::stdout().lock();
+    let line = sans.join(" ");
+    let Some(node) = tree.find(sans.iter().map(String::as_str)) else {
+        return writeln!(out, "{line}: not in the opening tree").map_err(Error::output);
+    };
+    writeln!(
+        out,
+        "{:<8}{node}",
+        if line.is_empty() {
+            "start"
+        } else {
+            line.as_str()
+        }
+    )
+    .map_err(Error::output)?;
+    node.moves()
+        .into_iter()
+        .try_for_each(|(san, child)| writeln!(out, "  {san:<6}{child}"))
+        .map_err(Error::output)
+}
+
+#[cfg(test)]
+#[allow(clippy::panic_in_result_fn)]
+mod tests {
+    use super::*;
+
+    type Error = crate::error::Source;
+    type Result<T> = std::result::Result<T, Error>;
+
+    fn game(
+        game_link: [u8; 8],
+        start_time: u32,
+        result: GameResult,
+        sans: &str,
+    ) -> Result<(GameData, Vec<San>)> {
+        let mut game_data = GameData {
+            start_time,
+            game_link,
+            result,
+            ..Default::default()
+        };
+        game_data.white_player.elo = 2000;
+        game_data.black_player.elo = 1800;
+        let sans = sans
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<std::result::Result<Vec<San>, _>>()?;
+        Ok((game_data, sans))
+    }
+
+    fn tree() -> Result<OpeningTree> {
+        let mut tree = OpeningTree::new(3);
+        for (game_link, start_time, result, sans) in [
+            (b"aaaaaaaa", 200, GameResult::WhiteWin, "e4 e5 Nf3 Nc6"),
+            (b"bbbbbbbb", 100, GameResult::Draw, "e4 c5 Nf3"),
+            (b"cccccccc", 300, GameResult::BlackWin, "d4 d5"),
+        ] {
+            let (game_data, sans) = game(*game_link, start_time, result, sans)?;
+            tree.add_game(&game_data, &sans);
+        }
+        Ok(tree)
+    }
+
+    #[test]
+    fn test_opening_tree() -> Result<()> {
+        let tree = tree()?;
+        let e4 = tree.find(["e4"]).ok_or("e4 missing")?;
+
+        assert_eq!(
+            (e4.games, e4.white_wins, e4.draws, e4.black_wins),
+            (2, 1, 1, 0)
+        );
+        assert_eq!(
+            (
+                e4.average_elo(),
+                e4.first_played.map(|first| first.game_link),
+                e4.moves().len()
+            ),
+            (Some(1900), Some(*b"bbbbbbbb"), 2)
+        );
+        // only the first three plies are kept
+        assert_eq!(
+            (
+                tree.find(["e4", "e5", "Nf3"]).is_some(),
+                tree.find(["e4", "e5", "Nf3", "Nc6"]).is_some()
+            ),
+            (true, false)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_opening_tree_merge() -> Result<()> {
+        let mut merged = tree()?;
+        merged.merge(tree()?);
+        let d4 = merged.find(["d4", "d5"]).ok_or("d4 d5 missing")?;
+
+        assert_eq!(merged.root.games, 6);
+        assert_eq!((d4.games, d4.black_wins), (2, 2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_opening_tree_binary_roundtrip() -> Result<()> {
+        let tree = tree()?;
+        let mut bytes = Vec::new();
+        tree.write_binary(&mut bytes)?;
+
+        assert_eq!(OpeningTree::read_binary(&bytes)?, tree);
+        assert!(OpeningTree::read_binary(&bytes[..bytes.len() - 1]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_opening_tree_keeps_its_files() -> Result<()> {
+        let mut tree = tree()?.with_file("2020-01");
+        tree.merge(OpeningTree::new(3).with_file("2020-02"));
+        let mut bytes = Vec::new();
+        tree.write_binary(&mut bytes)?;
+        let read = OpeningTree::read_binary(&bytes)?;
+
+        assert_eq!(
+            ["2020-01", "2020-02", "2020-03"].map(|label| read.contains_file(label)),
+            [true, true, false]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_opening_tree_json() -> Result<()> {
+        let mut json = Vec::new();
+        tree()?.write_json(&mut json, 2)?;
+        let value: serde_json::Value = serde_json::from_slice(&json)?;
+
+        assert_eq!(value["games"], 3);
+        assert_eq!(
+            value["children"]["e4"]["game_link"],
+            "https://lichess.org/bbbbbbbb"
+        );
+        // d4 was played once
+        assert!(value["children"].get("d4").is_none());
+        Ok(())
+    }
+}
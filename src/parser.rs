@@ -1,8 +1,10 @@
 use futures::{future::join_all, stream::FuturesUnordered, StreamExt};
 use itertools::Itertools;
 use pgn_reader::BufferedReader;
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rayon::iter::{Either, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use shakmaty::san::San;
 use std::{
+    collections::HashSet,
     fs::File,
     io::Read,
     path::Path,
@@ -10,50 +12,282 @@ use std::{
 };
 
 use crate::{
-    config::Config,
-    game_parser::{Game, GameData, Validator},
+    config::{Config, Dedupe, Filter, RareMovesFormat},
+    error::Error,
+    game_parser::{
+        Analysis, Game, GameData, GameFailure, ParsedGame, PositionCensus, RareMoveDetectors,
+        RareMoveWithLink, Validator,
+    },
+    openings::OpeningTree,
     plotter::Plotter,
+    records::Leaderboard,
     ui::{UserInterface, UI},
     util::{
-        from_file, get_data_output_file, get_file_list, get_move_output_file, save_file,
-        write_batch, write_moves, AndThenErr, FileInfo, Progress,
+        from_file, get_census_output_files, get_data_output_file, get_error_output_file,
+        get_file_label, get_file_list, get_leaderboard_output_file, get_move_output_file,
+        get_openings_output_files, get_rare_pgn_output_file, read_leaderboard,
+        read_previous_openings, save_file, write_batch, write_census, write_failures,
+        write_leaderboard, write_moves, write_openings, write_rare_pgns, FileInfo, Progress,
     },
     Result,
 };
 
-fn validate_and_log(game: Game, plotter: &Arc<Plotter>) -> Result<GameData> {
-    game.validate()
-        .inspect(|game_data| {
-            Plotter::add_samples(game_data, plotter);
+struct OutputFiles {
+    data: Option<File>,
+    moves: Option<(File, RareMovesFormat)>,
+    rare_pgns: Option<File>,
+    errors: Option<File>,
+}
+
+impl OutputFiles {
+    fn from_config(filename: &str, config: &Config) -> Result<Self> {
+        Ok(Self {
+            data: config
+                .output
+                .data
+                .then(|| get_data_output_file(filename))
+                .transpose()?,
+            moves: config
+                .output
+                .rare_moves
+                .then(|| {
+                    let format = config.output.rare_moves_format;
+                    get_move_output_file(filename, format).map(|file| (file, format))
+                })
+                .transpose()?,
+            rare_pgns: config
+                .output
+                .rare_pgn
+                .then(|| get_rare_pgn_output_file(filename))
+                .transpose()?,
+            errors: config
+                .output
+                .errors
+                .then(|| get_error_output_file(filename))
+                .transpose()?,
+        })
+    }
+
+    fn write(
+        &mut self,
+        data: &[GameData],
+        rare_moves: &[RareMoveWithLink],
+        rare_pgns: &[String],
+        failures: &[GameFailure],
+    ) -> Result<()> {
+        if let Some(data_output_file) = &mut self.data {
+            write_batch(data_output_file, data)?;
+        }
+        if let Some((move_output_file, format)) = &mut self.moves {
+            write_moves(move_output_file, rare_moves, *format)?;
+        }
+        if let Some(rare_pgn_output_file) = &mut self.rare_pgns {
+            write_rare_pgns(rare_pgn_output_file, rare_pgns)?;
+        }
+        if let Some(error_output_file) = &mut self.errors {
+            write_failures(error_output_file, failures)?;
+        }
+        Ok(())
+    }
+}
+
+// collected across every file of a run
+struct Shared {
+    records: Mutex<Leaderboard>,
+    census: Option<PositionCensus>,
+    openings: Option<Mutex<OpeningTree>>,
+    // position, move and type of every rare move written so far
+    rare_moves_seen: Option<Mutex<HashSet<String>>>,
+}
+
+impl Shared {
+    // with dedupe = "run" only the first game reaching a rare move in a position keeps it
+    fn first_in_run(&self, rare_moves: Vec<RareMoveWithLink>) -> Result<Vec<RareMoveWithLink>> {
+        let Some(seen) = &self.rare_moves_seen else {
+            return Ok(rare_moves);
+        };
+        let mut seen = seen.lock()?;
+        Ok(rare_moves
+            .into_iter()
+            .filter(|rare_move| seen.insert(rare_move.position_key()))
+            .collect())
+    }
+
+    // a file whose games are already in the tree of an earlier run is not added again
+    fn file_openings(&self, label: &str, config: &Config) -> Result<Option<OpeningTree>> {
+        let Some(openings) = &self.openings else {
+            return Ok(None);
+        };
+        if openings.lock()?.contains_file(label) {
+            return Ok(None);
+        }
+        Ok(config.openings.tree().map(|tree| tree.with_file(label)))
+    }
+}
+
+// what one file gathers over its batches
+struct FileState<'a> {
+    label: &'a str,
+    records: Leaderboard,
+    openings: Option<OpeningTree>,
+    shared: &'a Shared,
+}
+
+impl FileState<'_> {
+    fn opening_plies(&self) -> usize {
+        self.openings.as_ref().map_or(0, OpeningTree::plies)
+    }
+
+    // only games that were validated and passed the filter, none set up from a position
+    fn add_openings(&mut self, validated: &[Validated]) {
+        let Some(openings) = &mut self.openings else {
+            return;
+        };
+        validated
+            .iter()
+            .filter(|game| !game.data.from_position)
+            .for_each(|game| openings.add_game(&game.data, &game.opening));
+    }
+
+    fn add_census(&self, validated: &[Validated]) -> Result<()> {
+        let Some(census) = &self.shared.census else {
+            return Ok(());
+        };
+        validated
+            .par_iter()
+            .try_for_each(|game| census.add_game(&game.data, game.positions.iter().copied()))
+    }
+}
+
+#[derive(Default)]
+struct Batch {
+    games: Vec<Game>,
+    failures: Vec<GameFailure>,
+    filtered: u64,
+    skipped: u64,
+}
+
+impl Batch {
+    fn from_chunk(chunk: impl Iterator<Item = ParsedGame>, filter: &Filter) -> Self {
+        let mut batch = Self::default();
+        chunk.for_each(|parsed_game| match parsed_game {
+            ParsedGame::Parsed(game) if filter.accepts(&game.data) => batch.games.push(*game),
+            ParsedGame::Parsed(_) => batch.filtered += 1,
+            ParsedGame::Skipped(_) => batch.skipped += 1,
+            ParsedGame::Malformed(failure) => batch.failures.push(failure),
+        });
+        batch
+    }
+}
+
+struct Validated {
+    data: GameData,
+    // the annotated pgn of a game with rare moves, when rare_pgn is on
+    rare_pgn: Option<String>,
+    // the first plies for the opening tree, empty when it is off
+    opening: Vec<San>,
+    // the positions the game reached, empty when the census is off
+    positions: Vec<(u64, u16)>,
+}
+
+fn validate_games(
+    games: Vec<Game>,
+    plotter: &Arc<Plotter>,
+    analysis: &Analysis,
+    opening_plies: usize,
+) -> (Vec<Validated>, Vec<GameFailure>) {
+    games.into_par_iter().partition_map(|game| {
+        let opening = game.sans.iter().take(opening_plies).cloned().collect_vec();
+        match game.validate_with_pgn(analysis) {
+            Ok(replayed) => {
+                Plotter::add_samples(&replayed.data, plotter);
+                Either::Left(Validated {
+                    data: replayed.data,
+                    rare_pgn: replayed.rare_pgn,
+                    opening,
+                    positions: replayed.positions,
+                })
+            }
+            Err(failure) => Either::Right(failure),
+        }
+    })
+}
+
+// with dedupe = "run", a game whose rare moves were all seen in earlier games gets no pgn
+fn kept_rare_pgns(
+    data: &[GameData],
+    rare_pgns: Vec<Option<String>>,
+    rare_moves: &[RareMoveWithLink],
+) -> Vec<String> {
+    let games = rare_moves
+        .iter()
+        .map(|rare_move| rare_move.game_link.as_str())
+        .collect::<HashSet<_>>();
+    data.iter()
+        .zip(rare_pgns)
+        .filter_map(|(game_data, pgn)| {
+            pgn.filter(|_| {
+                games.contains(
+                    game_data
+                        .get_formatted_game_link()
+                        .unwrap_or_default()
+                        .as_str(),
+                )
+            })
         })
-        .and_then_err(|e| plotter.log_error(e))
+        .collect()
 }
 
 fn parse_batch(
-    chunk: impl Iterator<Item = Game>,
-    data_output_file: &mut Option<File>,
-    move_output_file: &mut Option<File>,
+    chunk: impl Iterator<Item = ParsedGame>,
+    output_files: &mut OutputFiles,
     plotter: &Arc<Plotter>,
+    filter: &Filter,
+    analysis: &Analysis,
+    file: &mut FileState,
 ) -> Result<Progress> {
-    let data = chunk
-        .collect::<Vec<_>>()
-        .into_par_iter()
-        .flat_map(|game| validate_and_log(game, plotter))
-        .collect::<Vec<_>>();
+    let batch = Batch::from_chunk(chunk, filter);
+    let (validated, failures) =
+        validate_games(batch.games, plotter, analysis, file.opening_plies());
+    file.add_openings(&validated);
+    file.add_census(&validated)?;
+    let (data, rare_pgns): (Vec<_>, Vec<_>) = validated
+        .into_iter()
+        .map(|game| (game.data, game.rare_pgn))
+        .unzip();
+    let failures = batch
+        .failures
+        .into_iter()
+        .chain(failures)
+        .map(|failure| failure.in_file(file.label))
+        .collect_vec();
+
+    plotter.add_clock_samples(&data)?;
+    plotter.add_mate_pattern_samples(&data)?;
+    plotter.add_draw_samples(&data)?;
+    plotter.log_queen_down_wins(&data)?;
+    file.records.add_games(&data);
 
-    let rare_moves = data.iter().flat_map(GameData::get_rare_moves).collect_vec();
+    let rare_moves = file
+        .shared
+        .first_in_run(data.iter().flat_map(GameData::get_rare_moves).collect_vec())?;
+
+    let rare_pgns = kept_rare_pgns(&data, rare_pgns, &rare_moves);
 
     rare_moves
         .iter()
         .try_for_each(|rare_move| Plotter::log_rare_move(plotter, rare_move))?;
+    failures
+        .iter()
+        .try_for_each(|failure| plotter.log_failure(failure))?;
 
-    if let Some(data_output_file) = data_output_file {
-        write_batch(data_output_file, &data)?;
-    }
-    if let Some(move_output_file) = move_output_file {
-        write_moves(move_output_file, &rare_moves)?;
-    }
-    Ok(data.into())
+    output_files.write(&data, &rare_moves, &rare_pgns, &failures)?;
+    Ok(Progress {
+        filtered: batch.filtered,
+        skipped: batch.skipped,
+        failures: failures.iter().map(|failure| failure.reason).collect(),
+        ..data.into()
+    })
 }
 
 fn parse_all_games(
@@ -62,42 +296,102 @@ fn parse_all_games(
     ui: &Arc<Mutex<UI>>,
     plotter: &Arc<Plotter>,
     config: &Config,
+    shared: &Shared,
 ) -> Result<()> {
-    let mut validator = Validator::new();
+    let mut validator = Validator::new().keeping_headers(config.output.rare_pgn);
     let mut progress = Progress::default();
-
-    let mut data_output_file = if config.output.data {
-        Some(get_data_output_file(filename)?)
-    } else {
-        None
+    let mut output_files = OutputFiles::from_config(filename, config)?;
+    let file_label = get_file_label(filename);
+    let analysis = Analysis {
+        detectors: RareMoveDetectors::new(&config.detectors.enabled),
+        forced_mates: config.forced_mates.search(),
+        census: shared.census.is_some(),
+        rare_pgn: config.output.rare_pgn,
     };
-
-    let mut move_output_file = if config.output.rare_moves {
-        Some(get_move_output_file(filename)?)
-    } else {
-        None
+    let mut file = FileState {
+        label: file_label,
+        records: config.records.leaderboard(),
+        openings: shared.file_openings(file_label, config)?,
+        shared,
     };
 
-    game_stream
+    let parsed = game_stream
         .into_iter(&mut validator)
         .flatten()
         .chunks(10000)
         .into_iter()
         .try_for_each(|chunk| {
-            progress += parse_batch(chunk, &mut data_output_file, &mut move_output_file, plotter)?;
+            progress += parse_batch(
+                chunk,
+                &mut output_files,
+                plotter,
+                &config.filter,
+                &analysis,
+                &mut file,
+            )?;
             UI::update_progress(ui, filename, progress)?;
             plotter.update()
-        })
-        .or_else(|e| UI::set_error(ui, filename, &e))?;
+        });
+    if parsed.is_err() {
+        // a partly added file would be skipped by every later run
+        file.openings = None;
+    }
+    parsed.or_else(|e| UI::set_error(ui, filename, &e.in_file(file_label)))?;
 
+    save_shared(shared, config, file.records, file.openings)?;
     UI::complete_file(ui, filename, progress)
 }
 
+fn save_shared(
+    shared: &Shared,
+    config: &Config,
+    file_records: Leaderboard,
+    file_openings: Option<OpeningTree>,
+) -> Result<()> {
+    save_leaderboard(&shared.records, file_records)?;
+    if let Some(census) = &shared.census {
+        save_census(census)?;
+    }
+    if let (Some(openings), Some(file_openings)) = (&shared.openings, file_openings) {
+        save_openings(openings, file_openings, config.openings.json_min_games)?;
+    }
+    Ok(())
+}
+
+// rewritten after every file, so the leaderboard covers everything processed so far
+fn save_leaderboard(records: &Mutex<Leaderboard>, file_records: Leaderboard) -> Result<()> {
+    let mut records = records.lock()?;
+    records.merge(file_records);
+    // held while writing, so a later file cannot be overwritten by an earlier one
+    let written = write_leaderboard(&mut get_leaderboard_output_file()?, &records);
+    drop(records);
+    written
+}
+
+// positions seen in a single game so far, later files may still reach them
+fn save_census(census: &PositionCensus) -> Result<()> {
+    write_census(&mut get_census_output_files()?, &census.report()?)
+}
+
+// every month processed so far, the query subcommand reads the binary
+fn save_openings(
+    openings: &Mutex<OpeningTree>,
+    file_openings: OpeningTree,
+    json_min_games: u64,
+) -> Result<()> {
+    let mut openings = openings.lock()?;
+    openings.merge(file_openings);
+    let written = write_openings(&mut get_openings_output_files()?, &openings, json_min_games);
+    drop(openings);
+    written
+}
+
 async fn parse_file(
     file_info: FileInfo,
     ui: &Arc<Mutex<UI>>,
     plotter: &Arc<Plotter>,
     config: &Config,
+    shared: &Shared,
 ) -> Result<()> {
     if !config.years.contains(&file_info.year) {
         return Ok(());
@@ -115,7 +409,9 @@ async fn parse_file(
     UI::set_processing(ui, &filename)?;
     let game_stream = from_file(&filename).await?;
 
-    tokio::task::block_in_place(|| parse_all_games(&filename, game_stream, ui, plotter, config))
+    tokio::task::block_in_place(|| {
+        parse_all_games(&filename, game_stream, ui, plotter, config, shared)
+    })
 }
 
 fn spawn_parse_file(
@@ -123,8 +419,9 @@ fn spawn_parse_file(
     ui: Arc<Mutex<UI>>,
     plotter: Arc<Plotter>,
     config: Config,
+    shared: Arc<Shared>,
 ) -> tokio::task::JoinHandle<Result<()>> {
-    tokio::spawn(async move { parse_file(file_info, &ui, &plotter, &config).await })
+    tokio::spawn(async move { parse_file(file_info, &ui, &plotter, &config, &shared).await })
 }
 
 async fn push_until_full(
@@ -147,15 +444,49 @@ async fn collect(
         .collect::<Result<Vec<_>>>()
 }
 
+// records from earlier runs stay on the leaderboard
+fn load_leaderboard(config: &Config) -> Result<Leaderboard> {
+    let mut records = config.records.leaderboard();
+    if let Some(previous) = read_leaderboard()? {
+        records.load(&previous).map_err(Error::output)?;
+    }
+    Ok(records)
+}
+
+// the tree of earlier runs keeps growing with the files it does not hold yet
+fn load_openings(config: &Config) -> Result<Option<OpeningTree>> {
+    let Some(tree) = config.openings.tree() else {
+        return Ok(None);
+    };
+    let Some(previous) = read_previous_openings()? else {
+        return Ok(Some(tree));
+    };
+    OpeningTree::read_binary(&previous)
+        .map(Some)
+        .map_err(Error::decode)
+}
+
 pub async fn run_all_files() -> Result<()> {
     let config = Config::from_file()?;
     let plotter = Plotter::new_arc(&config)?;
     let ui = UI::new_arc()?;
+    let shared = Arc::new(Shared {
+        records: Mutex::new(load_leaderboard(&config)?),
+        census: config.census.position_census(),
+        openings: load_openings(&config)?.map(Mutex::new),
+        rare_moves_seen: (config.output.dedupe == Dedupe::Run).then(|| Mutex::new(HashSet::new())),
+    });
 
     let mut futures = FuturesUnordered::new();
 
     for file_info in get_file_list().await? {
-        let future = spawn_parse_file(file_info, ui.clone(), plotter.clone(), config.clone());
+        let future = spawn_parse_file(
+            file_info,
+            ui.clone(),
+            plotter.clone(),
+            config.clone(),
+            shared.clone(),
+        );
         push_until_full(&mut futures, future).await;
     }
 
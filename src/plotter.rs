@@ -1,26 +1,101 @@
 use atomic_time::AtomicInstant;
 use std::{
+    collections::{BTreeMap, HashSet},
     net::{IpAddr, Ipv4Addr, SocketAddr},
     sync::{
         atomic::{AtomicI64, Ordering},
-        Arc,
+        Arc, Mutex,
     },
     time::{Duration, Instant},
 };
 
 use crate::{
     config::Config,
-    game_parser::{GameData, GamePlayerData, RareMoveWithLink},
-    Error, Result,
+    error::Error,
+    game_parser::{
+        Clock, DrawType, GameData, GameFailure, GamePlayerData, MatePattern, RareMoveWithLink,
+    },
+    Result,
 };
 
+#[derive(Debug, Default, Clone, Copy)]
+struct ClockStats {
+    games: u64,
+    missed_wins: u64,
+    comebacks: u64,
+}
+
+#[derive(Debug, Default, Clone)]
+struct MatePatternStats {
+    by_elo: BTreeMap<(MatePattern, i16), u64>,
+    by_clock: BTreeMap<(MatePattern, Clock), u64>,
+    seen: HashSet<MatePattern>,
+}
+
+impl MatePatternStats {
+    // returns the patterns seen for the first time, with the game they were seen in
+    fn add(&mut self, game_data: &GameData) -> Vec<(MatePattern, String)> {
+        let elo_band = game_data
+            .get_winner()
+            .map_or(0, |winner| winner.elo / 200 * 200);
+        game_data
+            .mate_patterns
+            .patterns()
+            .filter_map(|pattern| {
+                *self.by_elo.entry((pattern, elo_band)).or_default() += 1;
+                *self.by_clock.entry((pattern, game_data.clock)).or_default() += 1;
+                self.seen.insert(pattern).then(|| {
+                    let game_link = game_data.get_formatted_game_link().unwrap_or_default();
+                    (pattern, game_link)
+                })
+            })
+            .collect()
+    }
+}
+
+// every count is kept per elo band
+#[derive(Debug, Default, Clone)]
+struct DrawStats {
+    games: BTreeMap<i16, u64>,
+    draws: BTreeMap<(DrawType, i16), u64>,
+    unclaimed: BTreeMap<(DrawType, i16), u64>,
+}
+
+impl DrawStats {
+    fn add(&mut self, game_data: &GameData) {
+        let elo = game_data.white_player.elo / 2 + game_data.black_player.elo / 2;
+        let elo_band = elo / 200 * 200;
+        *self.games.entry(elo_band).or_default() += 1;
+        if game_data.draw_type != DrawType::None {
+            *self
+                .draws
+                .entry((game_data.draw_type, elo_band))
+                .or_default() += 1;
+        }
+        if game_data.unclaimed_draw != DrawType::None {
+            *self
+                .unclaimed
+                .entry((game_data.unclaimed_draw, elo_band))
+                .or_default() += 1;
+        }
+    }
+
+    fn rate(&self, elo_band: i16, count: u64) -> f64 {
+        count as f64 / self.games.get(&elo_band).copied().unwrap_or(1) as f64
+    }
+}
+
 pub struct Plotter {
     rec: rerun::RecordingStream,
     elo_hist: Vec<AtomicI64>,
     missed_wins_hist: Vec<AtomicI64>,
     en_passant_hist: Vec<AtomicI64>,
     declined_en_passant_hist: Vec<AtomicI64>,
+    comeback_hist: Vec<AtomicI64>,
     half_moves_hist: Vec<AtomicI64>,
+    clock_stats: Mutex<BTreeMap<Clock, ClockStats>>,
+    mate_pattern_stats: Mutex<MatePatternStats>,
+    draw_stats: Mutex<DrawStats>,
     last_update: AtomicInstant,
     update_interval: Duration,
 }
@@ -30,7 +105,8 @@ impl Plotter {
         let port = config.port.unwrap_or(9876);
         let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::from(config.rerun_ip)), port);
         let rec = rerun::RecordingStreamBuilder::new("chess_analysis_evelyn")
-            .connect_opts(addr, Some(Duration::from_secs(1)))?;
+            .connect_opts(addr, Some(Duration::from_secs(1)))
+            .map_err(Error::plotting)?;
         // .spawn()?;
 
         Ok(Self {
@@ -39,7 +115,11 @@ impl Plotter {
             missed_wins_hist: Self::get_vec(4000),
             en_passant_hist: Self::get_vec(4000),
             declined_en_passant_hist: Self::get_vec(4000),
+            comeback_hist: Self::get_vec(4000),
             half_moves_hist: Self::get_vec(602),
+            clock_stats: Mutex::new(BTreeMap::new()),
+            mate_pattern_stats: Mutex::new(MatePatternStats::default()),
+            draw_stats: Mutex::new(DrawStats::default()),
             last_update: AtomicInstant::now(),
             update_interval: config.update_interval,
         })
@@ -61,6 +141,54 @@ impl Plotter {
         Self::add_player_samples(&game_data.white_player, plotter);
         Self::add_player_samples(&game_data.black_player, plotter);
         Self::add_sample(&plotter.half_moves_hist, game_data.half_moves as i16);
+        if let Some(winner) = game_data.get_winner().filter(|_| game_data.is_comeback()) {
+            Self::add_percentage_sample(&plotter.comeback_hist, winner.elo, 1);
+        }
+    }
+
+    pub fn add_clock_samples(&self, data: &[GameData]) -> Result<()> {
+        let mut clock_stats = self.clock_stats.lock()?;
+        for game_data in data {
+            let stats = clock_stats.entry(game_data.clock).or_default();
+            stats.games += 1;
+            stats.missed_wins += u64::from(game_data.white_player.missed_wins)
+                + u64::from(game_data.black_player.missed_wins);
+            stats.comebacks += u64::from(game_data.is_comeback());
+        }
+        drop(clock_stats);
+        Ok(())
+    }
+
+    pub fn log_queen_down_wins(&self, data: &[GameData]) -> Result<()> {
+        data.iter()
+            .filter(|game_data| game_data.won_down_a_queen())
+            .try_for_each(|game_data| {
+                let game_link = game_data.get_formatted_game_link().unwrap_or_default();
+                self.info(&format!("won down a queen: {game_link}"), None)
+            })
+    }
+
+    pub fn add_mate_pattern_samples(&self, data: &[GameData]) -> Result<()> {
+        let first_occurrences = {
+            let mut mate_pattern_stats = self.mate_pattern_stats.lock()?;
+            data.iter()
+                .flat_map(|game_data| mate_pattern_stats.add(game_data))
+                .collect::<Vec<_>>()
+        };
+        first_occurrences
+            .iter()
+            .try_for_each(|(pattern, game_link)| {
+                self.info(&format!("first {pattern} mate: {game_link}"), None)
+            })
+    }
+
+    pub fn add_draw_samples(&self, data: &[GameData]) -> Result<()> {
+        let mut draw_stats = self.draw_stats.lock()?;
+        for game_data in data {
+            draw_stats.add(game_data);
+        }
+        drop(draw_stats);
+        Ok(())
     }
 
     pub fn log_rare_move(plotter: &Self, rare_move: &RareMoveWithLink) -> Result<()> {
@@ -113,7 +241,70 @@ impl Plotter {
             &elo_buckets,
             "declined_en_passant_percentage",
         )?;
-        Ok(())
+        self.plot_percentage(&self.comeback_hist, &elo_buckets, "comeback_percentage")?;
+        self.plot_clock_stats()?;
+        self.plot_mate_pattern_stats()?;
+        self.plot_draw_stats()
+    }
+
+    fn plot_clock_stats(&self) -> Result<()> {
+        let clock_stats = self.clock_stats.lock()?.clone();
+        clock_stats.iter().try_for_each(|(clock, stats)| {
+            let missed_win_percentage = stats.missed_wins as f64 / (2 * stats.games) as f64;
+            self.plot_scalar(&format!("time_control/{clock}/games"), stats.games as f64)?;
+            self.plot_scalar(
+                &format!("time_control/{clock}/missed_win_percentage"),
+                missed_win_percentage,
+            )?;
+            self.plot_scalar(
+                &format!("time_control/{clock}/comeback_percentage"),
+                stats.comebacks as f64 / stats.games as f64,
+            )
+        })
+    }
+
+    fn plot_mate_pattern_stats(&self) -> Result<()> {
+        let mate_pattern_stats = self.mate_pattern_stats.lock()?.clone();
+        mate_pattern_stats
+            .by_elo
+            .iter()
+            .try_for_each(|((pattern, elo_band), count)| {
+                self.plot_scalar(
+                    &format!("mate_patterns/{pattern}/elo/{elo_band}"),
+                    *count as f64,
+                )
+            })?;
+        mate_pattern_stats
+            .by_clock
+            .iter()
+            .try_for_each(|((pattern, clock), count)| {
+                self.plot_scalar(
+                    &format!("mate_patterns/{pattern}/time_control/{clock}"),
+                    *count as f64,
+                )
+            })
+    }
+
+    fn plot_draw_stats(&self) -> Result<()> {
+        let draw_stats = self.draw_stats.lock()?.clone();
+        draw_stats
+            .draws
+            .iter()
+            .try_for_each(|((draw_type, elo_band), count)| {
+                self.plot_scalar(
+                    &format!("draws/{draw_type}/elo/{elo_band}"),
+                    draw_stats.rate(*elo_band, *count),
+                )
+            })?;
+        draw_stats
+            .unclaimed
+            .iter()
+            .try_for_each(|((draw_type, elo_band), count)| {
+                self.plot_scalar(
+                    &format!("draws/unclaimed_{draw_type}/elo/{elo_band}"),
+                    draw_stats.rate(*elo_band, *count),
+                )
+            })
     }
 
     fn to_buckets(atomics: &[AtomicI64]) -> Vec<f64> {
@@ -140,21 +331,26 @@ impl Plotter {
 
     fn plot(&self, buckets: &Vec<f64>, name: &str) -> Result<()> {
         let chart = rerun::BarChart::new(buckets.as_slice());
-        self.rec.log(name, &chart)?;
-        Ok(())
+        self.rec.log(name, &chart).map_err(Error::plotting)
+    }
+
+    fn plot_scalar(&self, name: &str, value: f64) -> Result<()> {
+        self.rec
+            .log(name, &rerun::Scalar::new(value))
+            .map_err(Error::plotting)
     }
 
     pub fn info(&self, message: &str, level: Option<&str>) -> Result<()> {
         let level = level.unwrap_or(rerun::TextLogLevel::INFO);
         let log = rerun::TextLog::new(message).with_level(level);
-        Ok(self.rec.log("logs", &log)?)
+        self.rec.log("logs", &log).map_err(Error::plotting)
     }
 
     pub fn error(&self, message: &str) -> Result<()> {
         self.info(message, Some(rerun::TextLogLevel::ERROR))
     }
 
-    pub fn log_error(&self, err: &Error) -> Result<()> {
-        self.error(&err.to_string())
+    pub fn log_failure(&self, failure: &GameFailure) -> Result<()> {
+        self.error(&failure.error.report())
     }
 }
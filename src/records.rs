@@ -0,0 +1,350 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap},
+    fmt::Display,
+    str::FromStr,
+};
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+use shakmaty::Role;
+
+use crate::{
+    error::{Cause, Source},
+    game_parser::{GameData, GamePlayerData},
+};
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    // queens of both sides on the board at the same time
+    Queens,
+    QueensOneSide,
+    RooksOneSide,
+    BishopsOneSide,
+    KnightsOneSide,
+    Promotions,
+    HalfMoves,
+    MoveVariations,
+    MissedMatesOneSide,
+    MissedForcedMatesOneSide,
+    // rating of the strongest player who let a mate in one go
+    MissedMateElo,
+}
+
+impl Metric {
+    pub const ALL: [Self; 11] = [
+        Self::Queens,
+        Self::QueensOneSide,
+        Self::RooksOneSide,
+        Self::BishopsOneSide,
+        Self::KnightsOneSide,
+        Self::Promotions,
+        Self::HalfMoves,
+        Self::MoveVariations,
+        Self::MissedMatesOneSide,
+        Self::MissedForcedMatesOneSide,
+        Self::MissedMateElo,
+    ];
+
+    fn value(self, game_data: &GameData) -> u32 {
+        match self {
+            Self::Queens => u32::from(game_data.max_queens),
+            Self::QueensOneSide => one_side(game_data, |player| player.max_pieces(Role::Queen)),
+            Self::RooksOneSide => one_side(game_data, |player| player.max_pieces(Role::Rook)),
+            Self::BishopsOneSide => one_side(game_data, |player| player.max_pieces(Role::Bishop)),
+            Self::KnightsOneSide => one_side(game_data, |player| player.max_pieces(Role::Knight)),
+            Self::Promotions => {
+                u32::from(game_data.white_player.promotions)
+                    + u32::from(game_data.black_player.promotions)
+            }
+            Self::HalfMoves => u32::from(game_data.half_moves),
+            Self::MoveVariations => game_data.move_variations,
+            Self::MissedMatesOneSide => one_side(game_data, |player| player.missed_mates),
+            Self::MissedForcedMatesOneSide => {
+                one_side(game_data, |player| player.missed_forced_mates)
+            }
+            Self::MissedMateElo => one_side(game_data, |player| {
+                if player.missed_mates > 0 {
+                    u16::try_from(player.elo).unwrap_or_default()
+                } else {
+                    0
+                }
+            }),
+        }
+    }
+}
+
+fn one_side<T: Into<u32>>(game_data: &GameData, value: impl Fn(&GamePlayerData) -> T) -> u32 {
+    let white = value(&game_data.white_player).into();
+    white.max(value(&game_data.black_player).into())
+}
+
+impl Display for Metric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::Queens => "queens",
+            Self::QueensOneSide => "queens_one_side",
+            Self::RooksOneSide => "rooks_one_side",
+            Self::BishopsOneSide => "bishops_one_side",
+            Self::KnightsOneSide => "knights_one_side",
+            Self::Promotions => "promotions",
+            Self::HalfMoves => "half_moves",
+            Self::MoveVariations => "move_variations",
+            Self::MissedMatesOneSide => "missed_mates_one_side",
+            Self::MissedForcedMatesOneSide => "missed_forced_mates_one_side",
+            Self::MissedMateElo => "missed_mate_elo",
+        };
+        write!(f, "{s}")
+    }
+}
+
+impl FromStr for Metric {
+    type Err = Source;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|metric| metric.to_string() == s)
+            .ok_or_else(|| Cause::UnexpectedValue(s.to_string()).into())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Record {
+    pub value: u32,
+    pub game_link: String,
+    pub date: NaiveDate,
+}
+
+// the best games per metric, the weakest kept record on top of each heap
+#[derive(Debug, Clone)]
+pub struct Leaderboard {
+    size: usize,
+    metrics: Vec<Metric>,
+    tops: BTreeMap<Metric, BinaryHeap<Reverse<Record>>>,
+}
+
+impl Leaderboard {
+    pub const fn new(size: usize, metrics: Vec<Metric>) -> Self {
+        Self {
+            size,
+            metrics,
+            tops: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_games(&mut self, data: &[GameData]) {
+        for game_data in data {
+            self.add(game_data);
+        }
+    }
+
+    pub fn add(&mut self, game_data: &GameData) {
+        let records = self
+            .metrics
+            .iter()
+            .map(|&metric| (metric, metric.value(game_data)))
+            // building the link is the expensive part, most games never make it
+            .filter(|&(metric, value)| self.qualifies(metric, value))
+            .map(|(metric, value)| {
+                let record = Record {
+                    value,
+                    game_link: game_data.get_formatted_game_link().unwrap_or_default(),
+                    date: game_data.get_start_date().unwrap_or_default(),
+                };
+                (metric, record)
+            })
+            .collect::<Vec<_>>();
+        for (metric, record) in records {
+            self.push(metric, record);
+        }
+    }
+
+    // records of metrics that are not configured are dropped
+    pub fn merge(&mut self, other: Self) {
+        for (metric, top) in other.tops {
+            for Reverse(record) in top {
+                self.push(metric, record);
+            }
+        }
+    }
+
+    // reads back what Display wrote
+    pub fn load(&mut self, s: &str) -> Result<(), Source> {
+        s.lines().try_for_each(|line| {
+            let (metric, record) = parse_line(line)?;
+            self.push(metric, record);
+            Ok(())
+        })
+    }
+
+    // best first
+    pub fn records(&self, metric: Metric) -> Vec<&Record> {
+        let mut records = self
+            .tops
+            .get(&metric)
+            .map(|top| top.iter().map(|Reverse(record)| record).collect::<Vec<_>>())
+            .unwrap_or_default();
+        records.sort_unstable_by(|a, b| b.cmp(a));
+        records
+    }
+
+    fn qualifies(&self, metric: Metric, value: u32) -> bool {
+        self.tops.get(&metric).map_or(self.size > 0, |top| {
+            top.len() < self.size
+                || top
+                    .peek()
+                    .is_some_and(|Reverse(weakest)| value > weakest.value)
+        })
+    }
+
+    // a game is listed once per metric, with its best value, even when loaded and merged again
+    fn push(&mut self, metric: Metric, record: Record) {
+        if !self.metrics.contains(&metric) {
+            return;
+        }
+        let top = self.tops.entry(metric).or_default();
+        if top.iter().any(|Reverse(listed)| {
+            listed.game_link == record.game_link && listed.value >= record.value
+        }) {
+            return;
+        }
+        top.retain(|Reverse(listed)| listed.game_link != record.game_link);
+        top.push(Reverse(record));
+        if top.len() > self.size {
+            top.pop();
+        }
+    }
+}
+
+// metric,rank,value,date,game_link
+fn parse_line(line: &str) -> Result<(Metric, Record), Source> {
+    let [metric, _rank, value, date, game_link] = line.split(',').collect::<Vec<_>>()[..] else {
+        return Err(Cause::UnexpectedValue(line.to_string()).into());
+    };
+    let record = Record {
+        value: value.parse()?,
+        game_link: game_link.to_string(),
+        date: date.parse()?,
+    };
+    Ok((metric.parse()?, record))
+}
+
+impl Display for Leaderboard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for metric in &self.metrics {
+            for (rank, record) in self.records(*metric).iter().enumerate() {
+                writeln!(
+                    f,
+                    "{metric},{},{},{},{}",
+                    rank + 1,
+                    record.value,
+                    record.date,
+                    record.game_link
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::panic_in_result_fn)]
+mod tests {
+    use super::*;
+
+    type Error = crate::error::Source;
+    type Result<T> = std::result::Result<T, Error>;
+
+    fn leaderboard() -> Leaderboard {
+        Leaderboard::new(10, Metric::ALL.to_vec())
+    }
+
+    fn game(game_link: [u8; 8], max_queens: u8) -> GameData {
+        GameData {
+            game_link,
+            max_queens,
+            // 2024-06-09
+            start_time: 1_717_891_200,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_leaderboard_keeps_top_n() {
+        let data = (0..20)
+            .map(|queens| game([b'a' + queens; 8], queens))
+            .collect::<Vec<_>>();
+        let mut leaderboard = leaderboard();
+        leaderboard.add_games(&data);
+        let values = leaderboard
+            .records(Metric::Queens)
+            .iter()
+            .map(|record| record.value)
+            .collect::<Vec<_>>();
+
+        assert_eq!(values, (10..20).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_leaderboard_merge() {
+        let mut board = leaderboard();
+        board.add(&game(*b"aaaaaaaa", 5));
+        let mut other = leaderboard();
+        other.add(&game(*b"bbbbbbbb", 9));
+        board.merge(other);
+        let best = board.records(Metric::Queens)[0];
+
+        assert_eq!(best.value, 9);
+        assert_eq!(best.game_link, "https://lichess.org/bbbbbbbb");
+        assert_eq!(
+            board.to_string().lines().next(),
+            Some("queens,1,9,2024-06-09,https://lichess.org/bbbbbbbb")
+        );
+    }
+
+    #[test]
+    fn test_leaderboard_lists_a_game_once() {
+        let mut board = leaderboard();
+        board.add(&game(*b"aaaaaaaa", 5));
+        board.add(&game(*b"aaaaaaaa", 7));
+        board.add(&game(*b"aaaaaaaa", 6));
+        let mut other = leaderboard();
+        other.add(&game(*b"aaaaaaaa", 5));
+        board.merge(other);
+        let values = board
+            .records(Metric::Queens)
+            .iter()
+            .map(|record| record.value)
+            .collect::<Vec<_>>();
+
+        assert_eq!(values, [7]);
+    }
+
+    #[test]
+    fn test_leaderboard_configured_metrics() {
+        let mut game_data = game(*b"abcdefgh", 2);
+        game_data.half_moves = 300;
+        game_data.black_player.missed_mates = 4;
+        game_data.black_player.elo = 2900;
+        let mut leaderboard = Leaderboard::new(3, vec![Metric::HalfMoves, Metric::MissedMateElo]);
+        leaderboard.add(&game_data);
+
+        assert!(leaderboard.records(Metric::Queens).is_empty());
+        assert_eq!(leaderboard.records(Metric::HalfMoves)[0].value, 300);
+        assert_eq!(leaderboard.records(Metric::MissedMateElo)[0].value, 2900);
+    }
+
+    #[test]
+    fn test_leaderboard_roundtrip() -> Result<()> {
+        let mut board = leaderboard();
+        board.add_games(&[game(*b"aaaaaaaa", 5), game(*b"bbbbbbbb", 9)]);
+        let mut loaded = leaderboard();
+        loaded.load(&board.to_string())?;
+
+        assert_eq!(loaded.to_string(), board.to_string());
+        assert!(loaded.load("queens,1,many,2024-06-09,link").is_err());
+        Ok(())
+    }
+}
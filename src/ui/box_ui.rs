@@ -24,9 +24,10 @@ use ratatui::{
 };
 
 use crate::{
+    error::{Cause, Error},
     ui::util::{get_elapsed_time, to_human},
-    util::{FileInfo, Progress},
-    Error, Result,
+    util::{FailureCounts, FileInfo, Progress},
+    Result,
 };
 
 use super::{
@@ -44,8 +45,8 @@ pub struct BoxUI {
 
 impl BoxUI {
     pub fn new() -> Result<Self> {
-        let terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
-        Self::setup()?;
+        let terminal = Terminal::new(CrosstermBackend::new(stdout())).map_err(Error::ui)?;
+        Self::setup().map_err(Error::ui)?;
         Ok(Self {
             file_info: HashMap::new(),
             terminal,
@@ -53,21 +54,29 @@ impl BoxUI {
         })
     }
 
-    fn setup() -> Result<()> {
+    fn setup() -> std::io::Result<()> {
         enable_raw_mode()?;
         stdout().execute(Clear(ClearType::All))?;
         stdout().execute(EnterAlternateScreen)?;
         Ok(())
     }
 
+    fn restore_terminal(&mut self) -> std::io::Result<()> {
+        disable_raw_mode()?;
+        stdout().execute(LeaveAlternateScreen)?;
+        self.terminal.show_cursor()?;
+        self.terminal.clear()
+    }
+
     fn update(&mut self) -> Result<()> {
         if self.last_update.elapsed().as_millis() < 500 {
             return Ok(());
         }
         self.last_update = Instant::now();
         self.terminal
-            .draw(|frame| Self::draw(&self.file_info, frame))?;
-        if handle_events()? {
+            .draw(|frame| Self::draw(&self.file_info, frame))
+            .map_err(Error::ui)?;
+        if handle_events().map_err(Error::ui)? {
             self.exit()?;
         }
         Ok(())
@@ -90,20 +99,23 @@ impl BoxUI {
             main_layout[0],
         );
         frame.render_widget(
-            Block::new().borders(Borders::TOP).title("Status Bar"),
+            Block::new()
+                .borders(Borders::TOP)
+                .title(create_status_bar(file_info_map)),
             main_layout[2],
         );
 
         let inner_layout = Layout::new(
             Direction::Horizontal,
             [
-                Constraint::Percentage(25),
+                Constraint::Percentage(22),
                 Constraint::Percentage(10),
                 Constraint::Percentage(10),
                 Constraint::Percentage(10),
                 Constraint::Percentage(10),
                 Constraint::Percentage(8),
-                Constraint::Percentage(27),
+                Constraint::Percentage(8),
+                Constraint::Percentage(22),
             ],
         )
         .split(main_layout[1]);
@@ -126,8 +138,9 @@ impl BoxUI {
         render_paragraph(frame, inner_layout[2], &files, create_speed);
         render_paragraph(frame, inner_layout[3], &files, create_move_variations);
         render_paragraph(frame, inner_layout[4], &files, create_move_speed);
-        render_paragraph(frame, inner_layout[5], &files, create_status);
-        render_paragraph(frame, inner_layout[6], &files, create_messages);
+        render_paragraph(frame, inner_layout[5], &files, create_failures);
+        render_paragraph(frame, inner_layout[6], &files, create_status);
+        render_paragraph(frame, inner_layout[7], &files, create_messages);
     }
 }
 
@@ -159,7 +172,8 @@ fn create_progress<'a>(files: &'a [&FileProgress]) -> Paragraph<'a> {
                 format!("{p:8.2}%")
             }
             _ => {
-                let p = 100.0 * fp.progress.games as f64 / fp.file_info.num_games as f64;
+                let p =
+                    100.0 * fp.progress.processed_games() as f64 / fp.file_info.num_games as f64;
                 format!("{p:8.2}%")
             }
         }
@@ -229,6 +243,31 @@ fn create_move_speed<'a>(files: &'a [&FileProgress]) -> Paragraph<'a> {
     )
 }
 
+fn create_failures<'a>(files: &'a [&FileProgress]) -> Paragraph<'a> {
+    fn reducer<'a>(file_info: &'a [&FileProgress]) -> Line<'a> {
+        let p: u64 = file_info
+            .iter()
+            .map(|fp| fp.progress.failures.total())
+            .sum();
+        Line::raw(format!("{:>8}", to_human(p as f64)))
+    }
+
+    create_paragraph(
+        files,
+        "Failed",
+        |fp| format!("{:>8}", to_human(fp.progress.failures.total() as f64)),
+        reducer,
+    )
+}
+
+fn create_status_bar(file_info_map: &HashMap<String, FileProgress>) -> String {
+    let mut failures = FailureCounts::default();
+    for fp in file_info_map.values() {
+        failures += fp.progress.failures;
+    }
+    format!("Failures: {failures}")
+}
+
 fn create_status<'a>(files: &'a [&FileProgress]) -> Paragraph<'a> {
     to_paragraph(create_lines(files, FileProgress::get_status), "Status")
 }
@@ -287,7 +326,7 @@ impl UserInterface for BoxUI {
     fn set_error(&mut self, filename: &str, err: &Error) {
         if let Some(file_info) = self.file_info.get_mut(filename) {
             file_info.status = FileStatus::Error;
-            file_info.message = Some(err.to_string());
+            file_info.message = Some(err.report());
         }
     }
 
@@ -310,7 +349,7 @@ impl UserInterface for BoxUI {
         if let Some(file_info) = self.file_info.get_mut(filename) {
             let processing_time = match file_info.status {
                 FileStatus::Processing { start_time } => start_time.elapsed(),
-                _ => Err(Error::Ui)?,
+                _ => Err(Error::ui(Cause::NotProcessing))?,
             };
             file_info.status = FileStatus::Done { processing_time };
         }
@@ -319,17 +358,14 @@ impl UserInterface for BoxUI {
 
     fn wait_for_exit(&mut self) -> Result<()> {
         self.update()?;
-        while !handle_events()? {
+        while !handle_events().map_err(Error::ui)? {
             thread::sleep(std::time::Duration::from_millis(500));
         }
         self.exit()
     }
 
     fn exit(&mut self) -> Result<()> {
-        disable_raw_mode()?;
-        stdout().execute(LeaveAlternateScreen)?;
-        self.terminal.show_cursor()?;
-        self.terminal.clear()?;
+        self.restore_terminal().map_err(Error::ui)?;
         std::process::exit(0);
     }
 }
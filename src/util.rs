@@ -2,10 +2,16 @@ mod file_util;
 mod helpers;
 mod lichess_util;
 mod progress;
-mod traits;
 
-pub use file_util::{from_file, write_batch, write_moves, FileInfo};
-pub use helpers::{get_data_output_file, get_move_output_file, is_double_disambiguation};
+pub use file_util::{
+    from_file, open_pgn, write_batch, write_census, write_failures, write_leaderboard, write_moves,
+    write_openings, write_rare_pgns, FileInfo,
+};
+pub use helpers::{
+    get_census_output_files, get_data_output_file, get_error_output_file, get_file_label,
+    get_leaderboard_output_file, get_move_output_file, get_openings_output_files,
+    get_rare_pgn_output_file, is_double_disambiguation, read_leaderboard, read_openings,
+    read_previous_openings, read_rare_moves, write_catalogue,
+};
 pub use lichess_util::{get_file_list, save_file};
-pub use progress::Progress;
-pub use traits::AndThenErr;
+pub use progress::{FailureCounts, Progress};
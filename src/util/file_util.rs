@@ -1,6 +1,7 @@
 use std::{
     fs::File,
-    io::{Read, Write},
+    io::{BufWriter, Read, Write},
+    path::Path,
     slice,
     str::FromStr,
 };
@@ -10,8 +11,12 @@ use tokio::io::AsyncRead;
 use tokio_util::io::SyncIoBridge;
 
 use crate::{
-    game_parser::{GameData, RareMoveWithLink},
-    Error, Result,
+    config::RareMovesFormat,
+    error::{Cause, Error, Source},
+    game_parser::{CensusReport, GameData, GameFailure, RareMoveWithLink},
+    openings::OpeningTree,
+    records::Leaderboard,
+    Result,
 };
 
 // counts: lichess_db_standard_rated_2013-01.pgn.zst 1
@@ -29,15 +34,15 @@ pub struct FileInfo {
 }
 
 impl FromStr for FileInfo {
-    type Err = Error;
+    type Err = Source;
 
-    fn from_str(s: &str) -> Result<Self> {
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
         let mut parts = s.split(' ');
 
         let remote_filename = parts
             .next()
             .map(str::to_string)
-            .ok_or(Error::InvalidFilename(s.to_string()))?;
+            .ok_or_else(|| Cause::InvalidFilename(s.to_string()))?;
 
         let url = format!("https://database.lichess.org/standard/{remote_filename}");
 
@@ -45,12 +50,11 @@ impl FromStr for FileInfo {
 
         let num_games = parts
             .next()
-            .ok_or(Error::InvalidFilename(s.to_string()))?
-            .parse()
-            .map_err(Error::ParseInt)?;
+            .ok_or_else(|| Cause::InvalidFilename(s.to_string()))?
+            .parse()?;
 
-        let year = filename[33..37].parse().map_err(Error::ParseInt)?;
-        let month = filename[38..40].parse().map_err(Error::ParseInt)?;
+        let year = filename[33..37].parse()?;
+        let month = filename[38..40].parse()?;
 
         Ok(Self {
             url,
@@ -67,7 +71,7 @@ pub fn to_local_filename(remote_filename: &str) -> String {
 }
 
 async fn open_file(filename: &str) -> Result<tokio::fs::File> {
-    Ok(tokio::fs::File::open(filename).await?)
+    tokio::fs::File::open(filename).await.map_err(Error::decode)
 }
 
 pub async fn from_file(filename: &str) -> Result<BufferedReader<impl Read>> {
@@ -76,9 +80,20 @@ pub async fn from_file(filename: &str) -> Result<BufferedReader<impl Read>> {
         .and_then(to_buffered_reader)
 }
 
+// a local dump, compressed like the lichess ones or not, or a game saved from the export endpoint
+pub fn open_pgn(path: &Path) -> Result<BufferedReader<Box<dyn Read>>> {
+    let file = File::open(path).map_err(Error::decode)?;
+    let reader: Box<dyn Read> = if path.extension().is_some_and(|extension| extension == "zst") {
+        Box::new(zstd::Decoder::new(file).map_err(Error::decode)?)
+    } else {
+        Box::new(file)
+    };
+    Ok(BufferedReader::new(reader))
+}
+
 fn to_buffered_reader(reader: impl AsyncRead + Unpin) -> Result<BufferedReader<impl Read>> {
     let bridge = SyncIoBridge::new(reader); //  this is Read
-    let decoder = zstd::Decoder::new(bridge)?;
+    let decoder = zstd::Decoder::new(bridge).map_err(Error::decode)?;
     Ok(BufferedReader::new(decoder))
 }
 
@@ -86,13 +101,55 @@ pub fn write_batch(file: &mut File, v: &[GameData]) -> Result<()> {
     let p = v.as_ptr().cast();
     let l = std::mem::size_of_val(v);
     let d = unsafe { slice::from_raw_parts(p, l) };
-    Ok(file.write_all(d)?)
+    file.write_all(d).map_err(Error::output)
 }
 
-pub fn write_moves(file: &mut File, v: &[RareMoveWithLink]) -> Result<()> {
-    v.iter().try_for_each(|rare_move| {
-        writeln!(file, "{rare_move}")?;
-        Ok(())
+pub fn write_moves(file: &mut File, v: &[RareMoveWithLink], format: RareMovesFormat) -> Result<()> {
+    match format {
+        RareMovesFormat::Text => v
+            .iter()
+            .try_for_each(|rare_move| writeln!(file, "{rare_move}").map_err(Error::output)),
+        RareMovesFormat::JsonLines => v.iter().try_for_each(|rare_move| {
+            serde_json::to_writer(&mut *file, rare_move).map_err(Error::output)?;
+            writeln!(file).map_err(Error::output)
+        }),
+    }
+}
+
+pub fn write_leaderboard(file: &mut File, leaderboard: &Leaderboard) -> Result<()> {
+    write!(file, "{leaderboard}").map_err(Error::output)
+}
+
+pub fn write_rare_pgns(file: &mut File, pgns: &[String]) -> Result<()> {
+    pgns.iter()
+        .try_for_each(|pgn| writeln!(file, "{pgn}").map_err(Error::output))
+}
+
+pub fn write_census((unique, common): &mut (File, File), report: &CensusReport) -> Result<()> {
+    write!(unique, "{report}")
+        .and_then(|()| write!(common, "{}", report.common()))
+        .map_err(Error::output)
+}
+
+pub fn write_openings(
+    (binary, json): &mut (File, File),
+    tree: &OpeningTree,
+    json_min_games: u64,
+) -> Result<()> {
+    let mut binary = BufWriter::new(binary);
+    tree.write_binary(&mut binary)
+        .and_then(|()| binary.flush())
+        .map_err(Error::output)?;
+    let mut json = BufWriter::new(json);
+    tree.write_json(&mut json, json_min_games)
+        .map_err(Error::output)?;
+    json.flush().map_err(Error::output)
+}
+
+pub fn write_failures(file: &mut File, v: &[GameFailure]) -> Result<()> {
+    v.iter().try_for_each(|failure| {
+        serde_json::to_writer(&mut *file, failure).map_err(Error::output)?;
+        writeln!(file).map_err(Error::output)
     })
 }
 
@@ -101,7 +158,7 @@ pub fn write_moves(file: &mut File, v: &[RareMoveWithLink]) -> Result<()> {
 mod tests {
     use super::*;
 
-    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+    type Result<T> = std::result::Result<T, Source>;
 
     #[test]
     fn test_year() -> Result<()> {
@@ -4,7 +4,13 @@ use std::{
     io::Read,
 };
 
-use crate::{game_parser::FirstMove, Result};
+use crate::{
+    catalogue::Catalogue,
+    config::RareMovesFormat,
+    error::{Cause, Error},
+    game_parser::FirstMove,
+    Result,
+};
 
 use futures::Stream;
 use itertools::Itertools;
@@ -104,15 +110,26 @@ pub fn to_game_stream(
 ) -> Result<BufferedReader<impl Read>> {
     let reader = StreamReader::new(stream); // this is AsyncRead
     let bridge = SyncIoBridge::new(reader); //  this is Read
-    Ok(zstd::Decoder::new(bridge).map(BufferedReader::new)?)
+    zstd::Decoder::new(bridge)
+        .map(BufferedReader::new)
+        .map_err(Error::decode)
 }
 
 fn raw_file_name(input_file: &str) -> Result<&str> {
-    Ok(input_file
+    input_file
         .split('/')
         .last()
         .and_then(|s| s.split('.').next())
-        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "No filename found"))?)
+        .ok_or_else(|| Error::output(Cause::InvalidFilename(input_file.to_string())))
+}
+
+// "./data/lichess_db_standard_rated_2019-07.pgn.zst" -> "2019-07"
+pub fn get_file_label(input_file: &str) -> &str {
+    input_file
+        .rsplit('_')
+        .next()
+        .and_then(|s| s.split('.').next())
+        .unwrap_or(input_file)
 }
 
 fn open_or_create_file(filename: &str) -> Result<File> {
@@ -125,7 +142,7 @@ fn open_or_create_file(filename: &str) -> Result<File> {
     } else {
         File::create(filename)
     };
-    Ok(result?)
+    result.map_err(Error::output)
 }
 
 pub fn get_data_output_file(input_file: &str) -> Result<File> {
@@ -134,12 +151,83 @@ pub fn get_data_output_file(input_file: &str) -> Result<File> {
     open_or_create_file(&filename)
 }
 
-pub fn get_move_output_file(input_file: &str) -> Result<File> {
+pub fn get_move_output_file(input_file: &str, format: RareMovesFormat) -> Result<File> {
+    let raw_filename = raw_file_name(input_file)?;
+    let filename = match format {
+        RareMovesFormat::Text => format!("./output/{raw_filename}.moves"),
+        RareMovesFormat::JsonLines => format!("./output/{raw_filename}.moves.jsonl"),
+    };
+    open_or_create_file(&filename)
+}
+
+pub fn get_rare_pgn_output_file(input_file: &str) -> Result<File> {
     let raw_filename = raw_file_name(input_file)?;
-    let filename = format!("./output/{raw_filename}.moves");
+    let filename = format!("./output/{raw_filename}.rare.pgn");
     open_or_create_file(&filename)
 }
 
+pub fn get_error_output_file(input_file: &str) -> Result<File> {
+    let raw_filename = raw_file_name(input_file)?;
+    let filename = format!("./output/{raw_filename}.errors.jsonl");
+    open_or_create_file(&filename)
+}
+
+const LEADERBOARD_FILE: &str = "./output/leaderboard.csv";
+
+pub fn get_leaderboard_output_file() -> Result<File> {
+    open_or_create_file(LEADERBOARD_FILE)
+}
+
+pub fn read_leaderboard() -> Result<Option<String>> {
+    if !std::path::Path::new(LEADERBOARD_FILE).exists() {
+        return Ok(None);
+    }
+    std::fs::read_to_string(LEADERBOARD_FILE)
+        .map(Some)
+        .map_err(Error::output)
+}
+
+const OPENINGS_FILE: &str = "./output/openings.bin";
+
+pub fn get_openings_output_files() -> Result<(File, File)> {
+    Ok((
+        open_or_create_file(OPENINGS_FILE)?,
+        open_or_create_file("./output/openings.json")?,
+    ))
+}
+
+pub fn read_openings() -> Result<Vec<u8>> {
+    std::fs::read(OPENINGS_FILE).map_err(Error::decode)
+}
+
+pub fn read_previous_openings() -> Result<Option<Vec<u8>>> {
+    if !std::path::Path::new(OPENINGS_FILE).exists() {
+        return Ok(None);
+    }
+    read_openings().map(Some)
+}
+
+pub fn get_census_output_files() -> Result<(File, File)> {
+    Ok((
+        open_or_create_file("./output/unique_positions.csv")?,
+        open_or_create_file("./output/common_positions.csv")?,
+    ))
+}
+
+// the catalogue may not exist yet, a `.moves` file must
+pub fn read_rare_moves(path: &str, required: bool) -> Result<Option<String>> {
+    if !required && !std::path::Path::new(path).exists() {
+        return Ok(None);
+    }
+    std::fs::read_to_string(path)
+        .map(Some)
+        .map_err(Error::decode)
+}
+
+pub fn write_catalogue(path: &str, catalogue: &Catalogue) -> Result<()> {
+    std::fs::write(path, catalogue.to_string()).map_err(Error::output)
+}
+
 #[cfg(test)]
 #[allow(clippy::panic_in_result_fn)]
 mod tests {
@@ -150,6 +238,14 @@ mod tests {
 
     type Result<T, E> = std::result::Result<T, E>;
 
+    #[test]
+    fn test_get_file_label() {
+        assert_eq!(
+            get_file_label("./data/lichess_db_standard_rated_2019-07.pgn.zst"),
+            "2019-07"
+        );
+    }
+
     #[test]
     fn test_is_double_disambiguation() {
         let san = San::Normal {
@@ -13,7 +13,10 @@ use reqwest::{Client, Response};
 use tokio::{fs::File, io::AsyncWriteExt};
 use tokio_util::bytes::Bytes;
 
-use crate::{Error, Result};
+use crate::{
+    error::{Cause, Error},
+    Result,
+};
 
 use super::{file_util::FileInfo, helpers::to_game_stream};
 
@@ -24,14 +27,13 @@ pub async fn get_file_list() -> Result<Vec<FileInfo>> {
         .and_then(Response::text)
         .map_ok(|s| s.trim().to_string())
         .map_ok(|s| split_lines(&s))
-        .await?;
+        .await
+        .map_err(Error::download)?;
 
-    let file_infos = filenames
+    filenames
         .into_iter()
-        .map(|s| s.parse())
-        .collect::<Result<Vec<_>>>()?;
-
-    Ok(file_infos)
+        .map(|s| s.parse().map_err(Error::download))
+        .collect()
 }
 
 fn split_lines(s: &str) -> Vec<String> {
@@ -39,11 +41,12 @@ fn split_lines(s: &str) -> Vec<String> {
 }
 
 async fn download_file(url: &str) -> Result<Response> {
-    Ok(Client::new()
+    Client::new()
         .get(url)
         .send()
         .await
-        .and_then(Response::error_for_status)?)
+        .and_then(Response::error_for_status)
+        .map_err(Error::download)
 }
 
 async fn retry_download(url: &str) -> Result<Response> {
@@ -61,15 +64,17 @@ pub async fn save_file(
     callback: impl Fn(u64) -> Result<()> + Send,
 ) -> Result<()> {
     let response = retry_download(url).await?;
-    let size = response.content_length().ok_or(Error::NoContentLength)?;
+    let size = response
+        .content_length()
+        .ok_or_else(|| Error::download(Cause::NoContentLength))?;
     init(size)?;
     let mut progress = 0;
-    let mut file = File::create(filename).await?;
+    let mut file = File::create(filename).await.map_err(Error::download)?;
     let mut stream = response.bytes_stream().map_err(convert_error);
-    while let Some(chunk) = stream.try_next().await? {
+    while let Some(chunk) = stream.try_next().await.map_err(Error::download)? {
         progress += chunk.len() as u64;
         callback(progress)?;
-        file.write_all(&chunk).await?;
+        file.write_all(&chunk).await.map_err(Error::download)?;
     }
     Ok(())
 }
@@ -1,6 +1,9 @@
+use std::fmt::Display;
+
 use derive_more::AddAssign;
+use itertools::Itertools;
 
-use crate::game_parser::GameData;
+use crate::game_parser::{FailureReason, GameData};
 
 #[derive(Debug, Default, Copy, Clone, AddAssign)]
 pub struct Progress {
@@ -8,8 +11,14 @@ pub struct Progress {
     pub games: u64,
     pub moves: u64,
     pub move_variations: u64,
+    pub filtered: u64,
+    pub skipped: u64,
+    pub failures: FailureCounts,
 }
 
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct FailureCounts([u64; FailureReason::ALL.len()]);
+
 impl Progress {
     pub fn from_bytes(bytes: u64) -> Self {
         Self {
@@ -17,6 +26,10 @@ impl Progress {
             ..Default::default()
         }
     }
+
+    pub fn processed_games(&self) -> u64 {
+        self.games + self.filtered + self.skipped + self.failures.total()
+    }
 }
 
 impl From<Vec<GameData>> for Progress {
@@ -29,3 +42,70 @@ impl From<Vec<GameData>> for Progress {
         }
     }
 }
+
+impl FailureCounts {
+    pub const fn record(&mut self, reason: FailureReason) {
+        self.0[reason as usize] += 1;
+    }
+
+    pub fn total(&self) -> u64 {
+        self.0.iter().sum()
+    }
+
+    pub fn counts(&self) -> impl Iterator<Item = (FailureReason, u64)> + '_ {
+        FailureReason::ALL
+            .into_iter()
+            .map(|reason| (reason, self.0[reason as usize]))
+    }
+}
+
+impl std::ops::AddAssign for FailureCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.0
+            .iter_mut()
+            .zip(other.0)
+            .for_each(|(count, other_count)| *count += other_count);
+    }
+}
+
+impl FromIterator<FailureReason> for FailureCounts {
+    fn from_iter<T: IntoIterator<Item = FailureReason>>(iter: T) -> Self {
+        let mut counts = Self::default();
+        iter.into_iter().for_each(|reason| counts.record(reason));
+        counts
+    }
+}
+
+impl Display for FailureCounts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = self
+            .counts()
+            .filter(|(_, count)| *count > 0)
+            .map(|(reason, count)| format!("{reason}: {count}"))
+            .join(", ");
+        write!(f, "{s}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_counts() {
+        let mut counts: FailureCounts = [
+            FailureReason::IllegalMove,
+            FailureReason::BadDate,
+            FailureReason::IllegalMove,
+        ]
+        .into_iter()
+        .collect();
+        counts += std::iter::once(FailureReason::UnknownTermination).collect();
+
+        assert_eq!(counts.total(), 4);
+        assert_eq!(
+            counts.to_string(),
+            "unknown termination: 1, bad date: 1, illegal move: 2"
+        );
+    }
+}
@@ -0,0 +1,337 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use itertools::Itertools;
+use pgn_reader::BufferedReader;
+use shakmaty::{san::San, Chess, Move, Position};
+
+use crate::{
+    catalogue::Catalogue,
+    error::{Cause, Error},
+    game_parser::{
+        ForcedMateSearch, Game, GamePlayerData, MoveType, ParsedGame, RareMoveDetectors,
+        RareMoveWithLink, Validator,
+    },
+    util::{open_pgn, read_rare_moves},
+    Result,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    MissingGame,
+    // the game ends before the ply, or a move on the way there is illegal
+    NotReached,
+    IllegalMove,
+    NotPlayed { played: String },
+    NotMate,
+    NotStalemate,
+    NoForcedMate { depth: u8 },
+    // what the detectors make of the move instead
+    Classification { found: Vec<MoveType> },
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingGame => write!(f, "game not in the pgn source"),
+            Self::NotReached => write!(f, "ply not reached"),
+            Self::IllegalMove => write!(f, "illegal move"),
+            Self::NotPlayed { played } => write!(f, "{played} was played"),
+            Self::NotMate => write!(f, "not a checkmate"),
+            Self::NotStalemate => write!(f, "not a stalemate"),
+            Self::NoForcedMate { depth } => write!(f, "no forced mate in {depth}"),
+            Self::Classification { found } => {
+                write!(f, "classified as [{}]", found.iter().join("|"))
+            }
+        }
+    }
+}
+
+// the position before the ply and the move played there
+fn replay(game: &Game, ply: u16) -> std::result::Result<(Chess, Move), Mismatch> {
+    let first_ply = usize::from(game.start_position.turn().is_black());
+    let moves = usize::from(ply)
+        .checked_sub(first_ply)
+        .ok_or(Mismatch::NotReached)?;
+    let mut position = game.start_position.clone();
+    for san in game.sans.iter().take(moves) {
+        let m = san.to_move(&position).ok().ok_or(Mismatch::NotReached)?;
+        position.play_unchecked(&m);
+    }
+    let played = game
+        .sans
+        .get(moves)
+        .and_then(|san| san.to_move(&position).ok())
+        .ok_or(Mismatch::NotReached)?;
+    Ok((position, played))
+}
+
+fn check_outcome(
+    position: &Chess,
+    m: &Move,
+    move_type: &MoveType,
+) -> std::result::Result<(), Mismatch> {
+    if let MoveType::ForcedMate { depth } = *move_type {
+        return check_forced_mate(position, m, depth);
+    }
+    let mut after = position.clone();
+    after.play_unchecked(m);
+    match move_type {
+        MoveType::Stalemate { .. } if !after.is_stalemate() => Err(Mismatch::NotStalemate),
+        _ if move_type.is_checkmate() && !after.is_checkmate() => Err(Mismatch::NotMate),
+        _ => Ok(()),
+    }
+}
+
+// the recorded depth has to be the shortest mate the move forces
+fn check_forced_mate(position: &Chess, m: &Move, depth: u8) -> std::result::Result<(), Mismatch> {
+    let search = ForcedMateSearch {
+        max_depth: depth,
+        max_forcing_moves: usize::MAX,
+        log_min_elo: 0,
+    };
+    match (1..=depth).find(|&shortest| search.forces_mate(position, m, shortest)) {
+        Some(shortest) if shortest == depth => Ok(()),
+        Some(shortest) => Err(Mismatch::Classification {
+            found: vec![MoveType::ForcedMate { depth: shortest }],
+        }),
+        None => Err(Mismatch::NoForcedMate { depth }),
+    }
+}
+
+// the detectors of a parse run are the oracle for the type code
+fn check_classification(
+    position: &Chess,
+    m: &Move,
+    rare_move: &RareMoveWithLink,
+) -> std::result::Result<(), Mismatch> {
+    if !rare_move.move_type.is_checkmate() {
+        return Ok(());
+    }
+    let mut player = GamePlayerData::default();
+    player.check_rare_move(
+        &RareMoveDetectors::default(),
+        position,
+        m,
+        rare_move.ply.into(),
+        rare_move.move_type.was_played(),
+    );
+    let found = player
+        .rare_checkmates
+        .into_iter()
+        .map(|detected| detected.move_type)
+        .collect_vec();
+    if found.contains(&rare_move.move_type) {
+        Ok(())
+    } else {
+        Err(Mismatch::Classification { found })
+    }
+}
+
+pub fn verify(game: &Game, rare_move: &RareMoveWithLink) -> std::result::Result<(), Mismatch> {
+    let (position, played) = replay(game, rare_move.ply)?;
+    let m = San::from_ascii(rare_move.san.as_bytes())
+        .ok()
+        .and_then(|san| san.to_move(&position).ok())
+        .ok_or(Mismatch::IllegalMove)?;
+    if rare_move.move_type.was_played() && m != played {
+        return Err(Mismatch::NotPlayed {
+            played: San::from_move(&position, &played).to_string(),
+        });
+    }
+    check_outcome(&position, &m, &rare_move.move_type)?;
+    check_classification(&position, &m, rare_move)
+}
+
+fn game_id(game_link: &str) -> &str {
+    game_link.rsplit('/').next().unwrap_or(game_link)
+}
+
+// keeps the games of the rows out of a whole dump
+fn read_games(
+    pgn: BufferedReader<impl Read>,
+    ids: &HashSet<&str>,
+    games: &mut HashMap<String, Game>,
+) {
+    games.extend(
+        pgn.into_iter(&mut Validator::new())
+            .flatten()
+            .filter_map(ParsedGame::into_game)
+            .map(|game| (game.data.get_game_id(), game))
+            .filter(|(id, _)| ids.contains(id.as_str())),
+    );
+}
+
+// a directory holds one `<id>.pgn` per game, as the lichess game export endpoint returns it
+fn load_games(source: &str, ids: &HashSet<&str>) -> Result<HashMap<String, Game>> {
+    let mut games = HashMap::new();
+    if !Path::new(source).is_dir() {
+        read_games(open_pgn(Path::new(source))?, ids, &mut games);
+        return Ok(games);
+    }
+    for id in ids {
+        let path = Path::new(source).join(format!("{id}.pgn"));
+        if path.exists() {
+            read_games(open_pgn(&path)?, ids, &mut games);
+        }
+    }
+    Ok(games)
+}
+
+pub fn verify_all<'a>(
+    rare_moves: &'a [RareMoveWithLink],
+    games: &HashMap<String, Game>,
+) -> Vec<(&'a RareMoveWithLink, Mismatch)> {
+    rare_moves
+        .iter()
+        .filter_map(|rare_move| {
+            games
+                .get(game_id(&rare_move.game_link))
+                .ok_or(Mismatch::MissingGame)
+                .and_then(|game| verify(game, rare_move))
+                .err()
+                .map(|mismatch| (rare_move, mismatch))
+        })
+        .collect()
+}
+
+// `verify rare_checkmates.csv 2020-01.pgn.zst` replays every row in its game and prints the mismatches
+pub fn run(args: &[String]) -> Result<()> {
+    let [rows, source] = args else {
+        return Err(Error::config(Cause::UnexpectedValue(
+            "verify <catalogue> <pgn dump or export directory>".to_string(),
+        )));
+    };
+    let mut catalogue = Catalogue::default();
+    if let Some(s) = read_rare_moves(rows, true)? {
+        catalogue.load(&s).map_err(Error::decode)?;
+    }
+    let rare_moves = catalogue.rare_moves();
+    let ids = rare_moves
+        .iter()
+        .map(|rare_move| game_id(&rare_move.game_link))
+        .collect();
+    let mismatches = verify_all(rare_moves, &load_games(source, &ids)?);
+
+    let mut out = io::stdout().lock();
+    mismatches
+        .iter()
+        .try_for_each(|(rare_move, mismatch)| {
+            writeln!(out, "{}: {mismatch}", rare_move.to_string().trim())
+        })
+        .and_then(|()| {
+            writeln!(
+                out,
+                "{} of {} rows verified",
+                rare_moves.len() - mismatches.len(),
+                rare_moves.len()
+            )
+        })
+        .map_err(Error::output)
+}
+
+#[cfg(test)]
+#[allow(clippy::panic_in_result_fn)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    type Result<T> = std::result::Result<T, crate::error::Source>;
+
+    const PGN: &str = r#"[Event "Rated Blitz game"]
+[Site "https://lichess.org/abcd1234"]
+[Result "1-0"]
+[Variant "From Position"]
+[FEN "4rkr1/4p1p1/8/8/8/8/8/4K2R w K - 0 1"]
+[SetUp "1"]
+
+1. O-O# 1-0
+
+[Event "Rated Blitz game"]
+[Site "https://lichess.org/efgh5678"]
+[Result "1-0"]
+[Variant "From Position"]
+[FEN "3r3k/6pp/7N/8/2Q5/8/8/6K1 w - - 0 1"]
+[SetUp "1"]
+
+1. Qg8+ Rxg8 2. Nf7# 1-0
+
+"#;
+
+    fn games() -> HashMap<String, Game> {
+        let mut games = HashMap::new();
+        read_games(
+            BufferedReader::new(PGN.as_bytes()),
+            &HashSet::from(["abcd1234", "efgh5678"]),
+            &mut games,
+        );
+        games
+    }
+
+    #[rstest]
+    #[case("  1.   O-O#   ,OO   ,https://lichess.org/abcd1234", None)]
+    #[case("  1.   O-O#   ,OO  ?,https://lichess.org/abcd1234", None)]
+    #[case(
+        "  1.   O-O#   ,OO   ,https://lichess.org/zzzzzzzz",
+        Some(Mismatch::MissingGame)
+    )]
+    #[case(
+        "  2.   O-O#   ,OO   ,https://lichess.org/abcd1234",
+        Some(Mismatch::NotReached)
+    )]
+    #[case(
+        "  1.   Kxe8#  ,K x  ,https://lichess.org/abcd1234",
+        Some(Mismatch::IllegalMove)
+    )]
+    #[case(
+        "  1.   Rh8    ,OO  ?,https://lichess.org/abcd1234",
+        Some(Mismatch::NotMate)
+    )]
+    #[case(
+        "  1.   Kf2    ,K x  ,https://lichess.org/abcd1234",
+        Some(Mismatch::NotPlayed { played: "O-O".to_string() })
+    )]
+    #[case("  1.   Qg8    ,M2  ?,https://lichess.org/efgh5678", None)]
+    #[case(
+        "  1.   Nf7    ,M2  ?,https://lichess.org/efgh5678",
+        Some(Mismatch::NoForcedMate { depth: 2 })
+    )]
+    #[case(
+        "  1.   O-O    ,M2  ?,https://lichess.org/abcd1234",
+        Some(Mismatch::Classification { found: vec![MoveType::ForcedMate { depth: 1 }] })
+    )]
+    fn test_verify(#[case] row: &str, #[case] expected: Option<Mismatch>) -> Result<()> {
+        let rare_moves = [row.parse::<RareMoveWithLink>()?];
+        let mismatches = verify_all(&rare_moves, &games());
+
+        assert_eq!(
+            mismatches.into_iter().map(|(_, mismatch)| mismatch).next(),
+            expected
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_classification() -> Result<()> {
+        let rare_move: RareMoveWithLink =
+            "  1.   O-O#   ,OOO  ,https://lichess.org/abcd1234".parse()?;
+        let games = games();
+        let game = games.get("abcd1234").ok_or("game not read")?;
+
+        assert_eq!(
+            verify(game, &rare_move),
+            Err(Mismatch::Classification {
+                found: vec![MoveType::CastlingCheckmate {
+                    was_played: true,
+                    side: shakmaty::CastlingSide::KingSide,
+                }]
+            })
+        );
+        Ok(())
+    }
+}
@@ -34,6 +34,7 @@ class TimeControl(Enum):
     def format(self):
         return self.name.lower().split("_")[0]
 
+# mirrors the #[repr(C)] GamePlayerData, padding included
 game_player_data = np.dtype([
     ('name', "S20"),
     ('elo', np.int16),
@@ -43,6 +44,26 @@ game_player_data = np.dtype([
     ('missed_en_passant_mates', np.uint8),
     ('en_passants', np.uint8),
     ('declined_en_passants', np.uint8),
+    ('double_disambiguation_checkmates', np.uint8),
+    ('double_disambiguation_capture_checkmates', np.uint8),
+    ('underpromotions', np.uint8),
+    ('underpromotion_only_mates', np.uint8),
+    ('underpromotion_queen_also_mates', np.uint8),
+    ('stalemates', np.uint8),
+    ('stalemate_swindles', np.uint8),
+    ('missed_stalemate_swindles', np.uint8),
+    ('avoided_stalemates', np.uint8),
+    ('missed_forced_mates', np.uint8),
+    ('final_material', np.uint8),
+    ('max_deficit', np.uint8),
+    ('max_recovered_deficit', np.uint8),
+    ('padding', "V1"),
+    ('plies_behind', np.uint16),
+    ('max_piece_counts', (np.uint8, 6)),
+    ('promotions', np.uint8),
+    ('padding2', "V3"),
+    # the rare moves Vec, its pointer is meaningless on disk
+    ('rare_checkmates', "V24"),
 ])
 
 enriched_game_player_data = np.dtype(
@@ -53,17 +74,29 @@ enriched_game_player_data = np.dtype(
     ('time_control', np.uint8),
 ])
 
+# mirrors the #[repr(C)] GameData written to .data files, padding included
 game_data = np.dtype([
     ('white_player_data', game_player_data),
     ('black_player_data', game_player_data),
     ('start_time', np.uint32),
+    ('move_variations', np.uint32),
     ('game_link', "S8"),
+    ('base_seconds', np.uint16),
+    ('increment_seconds', np.uint16),
     ('time_control', np.uint8),
     ('result', np.uint8),
     ('termination', np.uint8),
     ('padding', np.uint8),
     ('half_moves', np.uint16),
-    ('padding2', np.uint16)
+    ('mate_patterns', np.uint16),
+    ('draw_type', np.uint8),
+    ('unclaimed_draw', np.uint8),
+    ('unclaimed_draw_ply', np.uint16),
+    ('max_queens', np.uint8),
+    ('padding2', np.uint8),
+    ('max_queens_ply', np.uint16),
+    ('from_position', np.bool_),
+    ('padding3', "V3"),
 ])
 
 termination_data = np.dtype([